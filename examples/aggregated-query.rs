@@ -43,5 +43,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         println!("Object in stream: {:?}", object);
     }
 
+    println!("Aggregated query a test collection with read time using Fluent API");
+    let results: Vec<FirestoreAggregatedQueryResult<MyAggTestStructure>> = db
+        .fluent()
+        .select()
+        .from(TEST_COLLECTION_NAME)
+        .aggregate(|a| a.fields([a.field(path!(MyAggTestStructure::counter)).count()]))
+        .obj()
+        .query_with_metadata()
+        .await?;
+
+    for result in results {
+        println!(
+            "Aggregated result: {:?} at {:?}",
+            result.result, result.read_time
+        );
+    }
+
     Ok(())
 }
@@ -46,9 +46,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             .add_to_batch(&mut current_batch)?;
 
         if idx % 100 == 0 {
-            let response = current_batch.write().await?;
+            let results = current_batch.write_with_handle_results().await?;
             current_batch = batch_writer.new_batch();
-            println!("{:?}", response);
+            for result in results {
+                println!(
+                    "{:?} {}: {:?}",
+                    result.handle.operation, result.handle.document_path, result.update_time
+                );
+            }
         }
     }
 
@@ -28,7 +28,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     const TEST_COLLECTION_NAME: &'static str = "test-batch-write";
 
     println!("Populating a test collection");
-    let (batch_writer, mut batch_results_reader) = db.create_streaming_batch_writer().await?;
+    let (batch_writer, mut batch_results_reader) = db
+        .create_streaming_batch_writer_with_options(
+            FirestoreStreamingBatchWriteOptions::new()
+                .with_requests_buffer_size(10)
+                .with_responses_buffer_size(10)
+                .with_max_in_flight_batches(20),
+        )
+        .await?;
 
     let response_thread = tokio::spawn(async move {
         while let Ok(Some(response)) = batch_results_reader.try_next().await {
@@ -57,6 +64,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         }
     }
 
+    current_batch.write().await?;
+    batch_writer.flush().await?;
+
     println!("Finishing...");
     batch_writer.finish().await;
     let _ = tokio::join!(response_thread);
@@ -0,0 +1,80 @@
+use firestore::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub fn config_env_var(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|e| format!("{}: {}", name, e))
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct MyTestStructure {
+    some_id: String,
+    some_num: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Logging with debug enabled
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter("firestore=debug")
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)?;
+
+    // Create an instance
+    let db = FirestoreDb::new(&config_env_var("PROJECT_ID")?).await?;
+
+    const TEST_COLLECTION_NAME: &str = "test-delete-by-query";
+    const TEST_CHILD_COLLECTION_NAME: &str = "test-delete-childs";
+
+    println!("Populating a test collection");
+    for idx in 0..20 {
+        let my_struct = MyTestStructure {
+            some_id: format!("test-{}", idx),
+            some_num: idx,
+        };
+
+        db.fluent()
+            .update()
+            .in_col(TEST_COLLECTION_NAME)
+            .document_id(&my_struct.some_id)
+            .object(&my_struct)
+            .execute()
+            .await?;
+
+        let parent_path = db.parent_path(TEST_COLLECTION_NAME, &my_struct.some_id)?;
+
+        db.fluent()
+            .update()
+            .in_col(TEST_CHILD_COLLECTION_NAME)
+            .document_id("child")
+            .parent(&parent_path)
+            .object(&my_struct)
+            .execute()
+            .await?;
+    }
+
+    println!("Deleting documents by query");
+    let progress = db
+        .fluent()
+        .delete()
+        .from(TEST_COLLECTION_NAME)
+        .filter(|q| q.for_all([q.field(path!(MyTestStructure::some_num)).less_than(10)]))
+        .recursive()
+        .execute()
+        .await?;
+
+    println!("Deleted by query: {:?}", progress);
+
+    println!("Deleting a document recursively");
+    let parent_path = db.parent_path(TEST_COLLECTION_NAME, "test-15")?;
+
+    db.recursive_delete_with_options(
+        parent_path.as_ref(),
+        FirestoreDeleteOptions::new().with_on_progress(Arc::new(|progress| {
+            println!("Deleted: {}, failed: {}", progress.deleted, progress.failed)
+        })),
+    )
+    .await?;
+
+    Ok(())
+}
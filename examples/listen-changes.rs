@@ -1,7 +1,5 @@
-use async_trait::async_trait;
 use chrono::prelude::*;
 use firestore::*;
-use rvstruct::ValueStruct;
 use serde::{Deserialize, Serialize};
 use std::io::Read;
 
@@ -24,51 +22,10 @@ struct MyTestStructure {
 
 const TEST_COLLECTION_NAME: &str = "test-listen";
 
-// The file where we store the cursor/token for the event when we read the last time
-const RESUME_TOKEN_FILENAME: &str = "last-read-token";
-
 // The IDs of targets - must be different for different listener targets/listeners in case you have many instances
 const TEST_TARGET_ID_BY_QUERY: FirestoreListenerTarget = FirestoreListenerTarget::new(42_i32);
 const TEST_TARGET_ID_BY_DOC_IDS: FirestoreListenerTarget = FirestoreListenerTarget::new(17_i32);
 
-#[derive(Clone)]
-pub struct TempFileTokenStorage;
-
-#[async_trait]
-impl FirestoreResumeStateStorage for TempFileTokenStorage {
-    async fn read_resume_state(
-        &self,
-        target: &FirestoreListenerTarget,
-    ) -> Result<Option<FirestoreListenerTargetResumeType>, Box<dyn std::error::Error + Send + Sync>>
-    {
-        let target_state_file_name = format!("{}.{}.tmp", RESUME_TOKEN_FILENAME, target.value());
-        let token = std::fs::read_to_string(target_state_file_name)
-            .ok()
-            .map(|str| {
-                hex::decode(&str)
-                    .map(FirestoreListenerToken::new)
-                    .map(FirestoreListenerTargetResumeType::Token)
-                    .map_err(|e| Box::new(e))
-            })
-            .transpose()?;
-
-        Ok(token)
-    }
-
-    async fn update_resume_token(
-        &self,
-        target: &FirestoreListenerTarget,
-        token: FirestoreListenerToken,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let target_state_file_name = format!("{}.{}.tmp", RESUME_TOKEN_FILENAME, target.value());
-
-        Ok(std::fs::write(
-            target_state_file_name,
-            hex::encode(token.value()),
-        )?)
-    }
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Logging with debug enabled
@@ -81,7 +38,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .await
         .unwrap();
 
-    let mut listener = db.create_listener(TempFileTokenStorage).await?;
+    // Storing the resume tokens in temporary files to continue after restarts.
+    // There are also in-memory and Firestore collection based storages available.
+    let mut listener = db
+        .create_listener(FirestoreFilesResumeStateStorage::temp())
+        .await?;
 
     let my_struct = MyTestStructure {
         doc_id: None,
@@ -115,15 +76,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     listener
         .start(|event| async move {
             match event {
-                FirestoreListenEvent::DocumentChange(ref doc_change) => {
-                    println!("Doc changed: {:?}", doc_change);
-
-                    if let Some(doc) = &doc_change.document {
-                        let obj: MyTestStructure =
-                            FirestoreDb::deserialize_doc_to::<MyTestStructure>(doc)
-                                .expect("Deserialized object");
-                        println!("As object: {:?}", obj);
-                    }
+                FirestoreListenEvent::Added(ref doc_change)
+                | FirestoreListenEvent::Modified(ref doc_change) => {
+                    println!("Doc changed: {:?}", doc_change.document_path());
+
+                    let obj: MyTestStructure = doc_change.obj().expect("Deserialized object");
+                    println!("As object: {:?}", obj);
+                }
+                FirestoreListenEvent::Removed(ref doc_remove)
+                | FirestoreListenEvent::Deleted(ref doc_remove) => {
+                    println!("Doc removed: {:?}", doc_remove.document_path);
                 }
                 _ => {
                     println!("Received a listen response event to handle: {:?}", event);
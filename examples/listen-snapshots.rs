@@ -0,0 +1,86 @@
+use chrono::prelude::*;
+use firestore::*;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+pub fn config_env_var(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|e| format!("{}: {}", name, e))
+}
+
+// Example structure to play with
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct MyTestStructure {
+    some_id: String,
+    some_string: String,
+    some_num: u64,
+
+    #[serde(with = "firestore::serialize_as_timestamp")]
+    created_at: DateTime<Utc>,
+}
+
+const TEST_COLLECTION_NAME: &str = "test-listen";
+
+const TEST_TARGET_ID_BY_QUERY: FirestoreListenerTarget = FirestoreListenerTarget::new(42_i32);
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Logging with debug enabled
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter("firestore=debug")
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)?;
+
+    let db = FirestoreDb::new(&config_env_var("PROJECT_ID")?)
+        .await
+        .unwrap();
+
+    let mut listener = db.create_snapshot_listener().await?;
+
+    db.fluent()
+        .select()
+        .from(TEST_COLLECTION_NAME)
+        .filter(|q| q.for_all([q.field("some_num").greater_than_or_equal(42)]))
+        .listen()
+        .add_snapshot_target(TEST_TARGET_ID_BY_QUERY, &mut listener)?;
+
+    listener
+        .start(|snapshot| async move {
+            println!(
+                "Snapshot for {:?} at {:?} contains {} docs",
+                snapshot.target,
+                snapshot.read_time,
+                snapshot.len()
+            );
+
+            for change in &snapshot.changes {
+                match change {
+                    FirestoreQuerySnapshotDocChange::Added(doc) => {
+                        println!("Added: {}", doc.name)
+                    }
+                    FirestoreQuerySnapshotDocChange::Modified(doc) => {
+                        println!("Modified: {}", doc.name)
+                    }
+                    FirestoreQuerySnapshotDocChange::Removed(document_path) => {
+                        println!("Removed: {}", document_path)
+                    }
+                }
+            }
+
+            let objs: Vec<MyTestStructure> = snapshot.obj()?;
+            println!("As objects: {:?}", objs);
+
+            Ok(())
+        })
+        .await?;
+
+    // Wait any input until we shutdown
+    println!(
+        "Waiting any other changes. Try firebase console to change in {} now yourself.",
+        TEST_COLLECTION_NAME
+    );
+    std::io::stdin().read(&mut [1])?;
+
+    listener.shutdown().await?;
+
+    Ok(())
+}
@@ -88,5 +88,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let as_vec: Vec<MyTestStructure> = object_stream.collect().await;
     println!("{:?}", as_vec);
 
+    println!("Querying a test collection using OR and nested filters");
+
+    let objects: Vec<MyTestStructure> = db
+        .fluent()
+        .select()
+        .from(TEST_COLLECTION_NAME)
+        .filter(|q| {
+            q.for_any([
+                q.field(path!(MyTestStructure::some_num)).eq(42),
+                q.for_all([
+                    q.field(path!(MyTestStructure::some_string)).eq("Test"),
+                    q.field(path!(MyTestStructure::one_more_string)).eq("Test2"),
+                ]),
+            ])
+        })
+        .obj()
+        .query()
+        .await?;
+
+    println!("{:?}", objects);
+
     Ok(())
 }
@@ -0,0 +1,85 @@
+use async_trait::async_trait;
+use firestore::*;
+use serde::{Deserialize, Serialize};
+
+pub fn config_env_var(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|e| format!("{}: {}", name, e))
+}
+
+// Example structure to play with
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct MyTestStructure {
+    test_string: String,
+}
+
+const TEST_COLLECTION_NAME: &str = "test-trans-fn";
+const TEST_DOCUMENT_ID: &str = "test_doc_id";
+
+// Transaction function with borrowed state
+struct AppendSuffix<'a> {
+    suffix: &'a str,
+}
+
+#[async_trait]
+impl<'a> FirestoreTransactionFn<usize> for AppendSuffix<'a> {
+    async fn run(
+        &self,
+        _db: FirestoreDb,
+        transaction: &mut FirestoreTransaction<'_>,
+    ) -> FirestoreResult<usize> {
+        let mut test_structure: MyTestStructure = transaction
+            .get_obj(TEST_COLLECTION_NAME, TEST_DOCUMENT_ID)
+            .await?;
+
+        test_structure.test_string += self.suffix;
+
+        transaction.update_object(
+            TEST_COLLECTION_NAME,
+            TEST_DOCUMENT_ID,
+            &test_structure,
+            None,
+            None,
+            vec![],
+        )?;
+
+        Ok(test_structure.test_string.len())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Logging with debug enabled
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter("firestore=debug")
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)?;
+
+    // Create an instance
+    let db = FirestoreDb::new(&config_env_var("PROJECT_ID")?).await?;
+
+    let _: MyTestStructure = db
+        .fluent()
+        .update()
+        .in_col(TEST_COLLECTION_NAME)
+        .document_id(TEST_DOCUMENT_ID)
+        .object(&MyTestStructure {
+            test_string: String::new(),
+        })
+        .execute()
+        .await?;
+
+    let suffix = String::from("abc");
+
+    let result = db
+        .run_transaction_fn(&AppendSuffix {
+            suffix: suffix.as_str(),
+        })
+        .await?;
+
+    println!(
+        "Updated string length: {}. Commit time: {:?}",
+        result.value, result.response.commit_time
+    );
+
+    Ok(())
+}
@@ -75,7 +75,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .document_id("test-5")
         .add_to_transaction(&mut transaction)?;
 
+    // Creating a new document, failing the transaction if it already exists
+    let new_document_id = db
+        .fluent()
+        .insert()
+        .into(TEST_COLLECTION_NAME)
+        .generate_document_id()
+        .object(&MyTestStructure {
+            some_id: format!("test-new"),
+            some_string: "NewTest".to_string(),
+        })
+        .add_to_transaction(&mut transaction)?;
+
     transaction.commit().await?;
+    println!("Created a new document {}", new_document_id);
 
     println!("Listing objects as a stream with updated test-0 and removed test-5");
     // Query as a stream our data
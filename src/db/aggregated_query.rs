@@ -1,6 +1,8 @@
 #![allow(clippy::derive_partial_eq_without_eq)] // Since we may not be able to implement Eq for the changes coming from Firestore protos
 
+use crate::timestamp_utils::from_timestamp;
 use crate::{FirestoreDb, FirestoreError, FirestoreQueryParams, FirestoreResult};
+use async_trait::async_trait;
 use chrono::prelude::*;
 use futures::future::BoxFuture;
 use futures::stream::BoxStream;
@@ -34,6 +36,10 @@ impl From<&FirestoreAggregation> for structured_aggregation_query::Aggregation {
     }
 }
 
+/// Aggregation operators available in the Firestore protos of the `gcloud-sdk` version in use.
+///
+/// These protos define only the `count` aggregation, so `sum` and `avg` aren't available yet:
+/// they need a `gcloud-sdk` release with the newer `StructuredAggregationQuery`.
 #[derive(Debug, PartialEq, Clone)]
 pub enum FirestoreAggregationOperator {
     Count(FirestoreAggregationOperatorCount),
@@ -62,11 +68,82 @@ impl From<&FirestoreAggregationOperatorCount> for structured_aggregation_query::
     }
 }
 
-impl FirestoreDb {
-    pub async fn aggregated_query_doc(
+#[derive(Debug, PartialEq, Clone, Builder)]
+pub struct FirestoreAggregatedQueryResult<T> {
+    pub result: T,
+    pub read_time: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait FirestoreAggregatedQuerySupport {
+    async fn aggregated_query_doc(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<Document>>;
+
+    async fn aggregated_query_doc_with_metadata(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<Document>>>;
+
+    async fn stream_aggregated_query_doc<'b>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, Document>>;
+
+    async fn stream_aggregated_query_doc_with_errors<'b>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<Document>>>;
+
+    async fn aggregated_query_obj<T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<T>>
+    where
+        for<'de> T: Deserialize<'de>;
+
+    async fn aggregated_query_obj_with_metadata<T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<T>>>
+    where
+        for<'de> T: Deserialize<'de>;
+
+    async fn stream_aggregated_query_obj<'b, T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, T>>
+    where
+        for<'de> T: Deserialize<'de>;
+
+    async fn stream_aggregated_query_obj_with_errors<'b, T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<T>>>
+    where
+        for<'de> T: Deserialize<'de>,
+        T: Send + 'b;
+}
+
+#[async_trait]
+impl FirestoreAggregatedQuerySupport for FirestoreDb {
+    async fn aggregated_query_doc(
         &self,
         params: FirestoreAggregatedQueryParams,
     ) -> FirestoreResult<Vec<Document>> {
+        Ok(self
+            .aggregated_query_doc_with_metadata(params)
+            .await?
+            .into_iter()
+            .map(|res| res.result)
+            .collect())
+    }
+
+    async fn aggregated_query_doc_with_metadata(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<Document>>> {
         let collection_str = params.query_params.collection_id.to_string();
 
         let span = span!(
@@ -79,7 +156,7 @@ impl FirestoreDb {
             .await
     }
 
-    pub async fn stream_aggregated_query_doc<'b>(
+    async fn stream_aggregated_query_doc<'b>(
         &self,
         params: FirestoreAggregatedQueryParams,
     ) -> FirestoreResult<BoxStream<'b, Document>> {
@@ -108,7 +185,7 @@ impl FirestoreDb {
         })))
     }
 
-    pub async fn stream_aggregated_query_doc_with_errors<'b>(
+    async fn stream_aggregated_query_doc_with_errors<'b>(
         &self,
         params: FirestoreAggregatedQueryParams,
     ) -> FirestoreResult<BoxStream<'b, FirestoreResult<Document>>> {
@@ -137,7 +214,7 @@ impl FirestoreDb {
         })))
     }
 
-    pub async fn aggregated_query_obj<T>(
+    async fn aggregated_query_obj<T>(
         &self,
         params: FirestoreAggregatedQueryParams,
     ) -> FirestoreResult<Vec<T>>
@@ -151,7 +228,26 @@ impl FirestoreDb {
             .collect()
     }
 
-    pub async fn stream_aggregated_query_obj<'b, T>(
+    async fn aggregated_query_obj_with_metadata<T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<T>>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let results = self.aggregated_query_doc_with_metadata(params).await?;
+        results
+            .into_iter()
+            .map(|res| {
+                Ok(
+                    FirestoreAggregatedQueryResult::new(Self::deserialize_doc_to(&res.result)?)
+                        .opt_read_time(res.read_time),
+                )
+            })
+            .collect()
+    }
+
+    async fn stream_aggregated_query_obj<'b, T>(
         &self,
         params: FirestoreAggregatedQueryParams,
     ) -> FirestoreResult<BoxStream<'b, T>>
@@ -173,7 +269,7 @@ impl FirestoreDb {
         })))
     }
 
-    pub async fn stream_aggregated_query_obj_with_errors<'b, T>(
+    async fn stream_aggregated_query_obj_with_errors<'b, T>(
         &self,
         params: FirestoreAggregatedQueryParams,
     ) -> FirestoreResult<BoxStream<'b, FirestoreResult<T>>>
@@ -186,12 +282,87 @@ impl FirestoreDb {
             future::ready(Self::deserialize_doc_to::<T>(&doc))
         })))
     }
+}
 
+// Inherent methods kept for the callers that don't import `FirestoreAggregatedQuerySupport`
+impl FirestoreDb {
+    pub async fn aggregated_query_doc(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<Document>> {
+        FirestoreAggregatedQuerySupport::aggregated_query_doc(self, params).await
+    }
+
+    pub async fn aggregated_query_doc_with_metadata(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<Document>>> {
+        FirestoreAggregatedQuerySupport::aggregated_query_doc_with_metadata(self, params).await
+    }
+
+    pub async fn stream_aggregated_query_doc<'b>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, Document>> {
+        FirestoreAggregatedQuerySupport::stream_aggregated_query_doc(self, params).await
+    }
+
+    pub async fn stream_aggregated_query_doc_with_errors<'b>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<Document>>> {
+        FirestoreAggregatedQuerySupport::stream_aggregated_query_doc_with_errors(self, params).await
+    }
+
+    pub async fn aggregated_query_obj<T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        FirestoreAggregatedQuerySupport::aggregated_query_obj(self, params).await
+    }
+
+    pub async fn aggregated_query_obj_with_metadata<T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<T>>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        FirestoreAggregatedQuerySupport::aggregated_query_obj_with_metadata(self, params).await
+    }
+
+    pub async fn stream_aggregated_query_obj<'b, T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        FirestoreAggregatedQuerySupport::stream_aggregated_query_obj(self, params).await
+    }
+
+    pub async fn stream_aggregated_query_obj_with_errors<'b, T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<T>>>
+    where
+        for<'de> T: Deserialize<'de>,
+        T: Send + 'b,
+    {
+        FirestoreAggregatedQuerySupport::stream_aggregated_query_obj_with_errors(self, params).await
+    }
+}
+
+impl FirestoreDb {
     fn create_aggregated_query_request(
         &self,
         params: &FirestoreAggregatedQueryParams,
     ) -> FirestoreResult<tonic::Request<RunAggregationQueryRequest>> {
-        Ok(tonic::Request::new(RunAggregationQueryRequest {
+        params.query_params.validate()?;
+        self.create_request(RunAggregationQueryRequest {
             parent: params
                 .query_params
                 .parent
@@ -210,7 +381,7 @@ impl FirestoreDb {
                     query_type: Some(gcloud_sdk::google::firestore::v1::structured_aggregation_query::QueryType::StructuredQuery(params.query_params.to_structured_query())),
                 }
             )),
-        }))
+        })
     }
 
     fn stream_aggregated_query_doc_with_retries<'a, 'b>(
@@ -280,7 +451,7 @@ impl FirestoreDb {
         params: FirestoreAggregatedQueryParams,
         retries: usize,
         span: &'a Span,
-    ) -> BoxFuture<'a, FirestoreResult<Vec<Document>>> {
+    ) -> BoxFuture<'a, FirestoreResult<Vec<FirestoreAggregatedQueryResult<Document>>>> {
         async move {
             let query_request = self.create_aggregated_query_request(&params)?;
             let begin_query_utc: DateTime<Utc> = Utc::now();
@@ -295,8 +466,11 @@ impl FirestoreDb {
                 Ok(query_response) => {
                     let query_stream = query_response
                         .into_inner()
-                        .map_ok(Self::aggregated_response_to_doc)
-                        .try_collect::<Vec<Option<Document>>>()
+                        .map_err(FirestoreError::from)
+                        .and_then(|agg_res| {
+                            future::ready(Self::aggregated_response_to_result(agg_res))
+                        })
+                        .try_collect::<Vec<Option<FirestoreAggregatedQueryResult<Document>>>>()
                         .await?
                         .into_iter()
                         .flatten()
@@ -338,6 +512,14 @@ impl FirestoreDb {
         .boxed()
     }
 
+    fn aggregated_response_to_result(
+        agg_res: RunAggregationQueryResponse,
+    ) -> FirestoreResult<Option<FirestoreAggregatedQueryResult<Document>>> {
+        let read_time = agg_res.read_time.clone().map(from_timestamp).transpose()?;
+        Ok(Self::aggregated_response_to_doc(agg_res)
+            .map(|doc| FirestoreAggregatedQueryResult::new(doc).opt_read_time(read_time)))
+    }
+
     fn aggregated_response_to_doc(mut agg_res: RunAggregationQueryResponse) -> Option<Document> {
         agg_res.result.take().map(|agg_res_doc| Document {
             name: "".to_string(),
@@ -1,9 +1,11 @@
+use crate::db::batch_writer::split_batch_writes;
 use crate::{
     FirestoreBatch, FirestoreBatchWriteResponse, FirestoreBatchWriter, FirestoreDb,
     FirestoreResult, FirestoreWriteResult,
 };
 use async_trait::async_trait;
 use gcloud_sdk::google::firestore::v1::{BatchWriteRequest, Write};
+use gcloud_sdk::google::rpc::Status;
 use rsb_derive::*;
 use std::collections::HashMap;
 use tracing::*;
@@ -40,31 +42,86 @@ impl FirestoreSimpleBatchWriter {
 impl FirestoreBatchWriter for FirestoreSimpleBatchWriter {
     type WriteResult = FirestoreBatchWriteResponse;
 
+    /// Writes exceeding the batch limits are sent in several requests,
+    /// and the results are combined in the order of the writes.
+    /// When a request fails as a whole, its writes get the status of the error,
+    /// so the results of the other requests are still returned.
     async fn write(&self, writes: Vec<Write>) -> FirestoreResult<FirestoreBatchWriteResponse> {
-        let response = self
-            .db
-            .client()
-            .get()
-            .batch_write(BatchWriteRequest {
-                database: self.db.get_database_path().to_string(),
-                writes,
-                labels: HashMap::new(),
-            })
-            .await?;
+        let chunks = split_batch_writes(writes)?;
 
-        let batch_response = response.into_inner();
+        if chunks.len() > 1 {
+            self.batch_span.in_scope(|| {
+                debug!(
+                    "Batch writes exceed the request limits and are split into {} requests",
+                    chunks.len()
+                )
+            });
+        }
 
-        let write_results: FirestoreResult<Vec<FirestoreWriteResult>> = batch_response
-            .write_results
+        let requests = chunks
             .into_iter()
-            .map(|s| s.try_into())
-            .collect();
-
-        Ok(FirestoreBatchWriteResponse::new(
-            0,
-            write_results?,
-            batch_response.status,
-        ))
+            .map(|chunk| {
+                let chunk_len = chunk.len();
+                self.db
+                    .create_request(BatchWriteRequest {
+                        database: self.db.get_database_path().to_string(),
+                        writes: chunk,
+                        labels: HashMap::new(),
+                    })
+                    .map(|request| (chunk_len, request))
+            })
+            .collect::<FirestoreResult<Vec<_>>>()?;
+
+        let mut write_results: Vec<FirestoreWriteResult> = Vec::new();
+        let mut statuses = Vec::new();
+
+        for (chunk_len, request) in requests {
+            let batch_response = match self.db.client().get().batch_write(request).await {
+                Ok(response) => response.into_inner(),
+                Err(status) => {
+                    self.batch_span.in_scope(|| {
+                        error!(
+                            "Batch write request with {} writes failed: {}",
+                            chunk_len, status
+                        )
+                    });
+                    add_failed_request_results(
+                        &mut write_results,
+                        &mut statuses,
+                        chunk_len,
+                        &status,
+                    );
+                    continue;
+                }
+            };
+
+            let chunk_write_results: FirestoreResult<Vec<FirestoreWriteResult>> = batch_response
+                .write_results
+                .into_iter()
+                .map(|s| s.try_into())
+                .collect();
+
+            write_results.extend(chunk_write_results?);
+            statuses.extend(batch_response.status);
+        }
+
+        Ok(FirestoreBatchWriteResponse::new(0, write_results, statuses))
+    }
+}
+
+pub(crate) fn add_failed_request_results(
+    write_results: &mut Vec<FirestoreWriteResult>,
+    statuses: &mut Vec<Status>,
+    writes_count: usize,
+    status: &tonic::Status,
+) {
+    for _ in 0..writes_count {
+        write_results.push(FirestoreWriteResult::new(vec![]));
+        statuses.push(Status {
+            code: status.code() as i32,
+            message: status.message().to_string(),
+            details: vec![],
+        });
     }
 }
 
@@ -81,3 +138,38 @@ impl FirestoreDb {
         FirestoreSimpleBatchWriter::new(self.clone(), options).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FirestoreBatchWriteHandle, FirestoreBatchWriteOperation};
+
+    #[test]
+    fn failed_request_results_keep_other_results() {
+        let mut write_results = vec![FirestoreWriteResult::new(vec![])];
+        let mut statuses = vec![Status {
+            code: tonic::Code::Ok as i32,
+            message: "".to_string(),
+            details: vec![],
+        }];
+
+        add_failed_request_results(
+            &mut write_results,
+            &mut statuses,
+            2,
+            &tonic::Status::unavailable("unavailable"),
+        );
+
+        let response = FirestoreBatchWriteResponse::new(0, write_results, statuses);
+        let handle = |index| FirestoreBatchWriteHandle {
+            index,
+            document_path: format!("docs/{}", index),
+            operation: FirestoreBatchWriteOperation::Delete,
+        };
+
+        assert!(response.handle_result(&handle(0)).unwrap().is_success());
+        assert!(!response.handle_result(&handle(1)).unwrap().is_success());
+        assert!(!response.handle_result(&handle(2)).unwrap().is_success());
+        assert!(response.handle_result(&handle(3)).is_none());
+    }
+}
@@ -1,18 +1,24 @@
+use crate::db::batch_simple_writer::add_failed_request_results;
+use crate::db::batch_writer::check_batch_writes_limits;
+use crate::errors::*;
 use crate::{
     FirestoreBatch, FirestoreBatchWriteResponse, FirestoreBatchWriter, FirestoreDb,
     FirestoreResult, FirestoreWriteResult,
 };
 use async_trait::async_trait;
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoffBuilder;
 use futures::stream::BoxStream;
-use futures::{StreamExt, TryStreamExt};
-use gcloud_sdk::google::firestore::v1::{Write, WriteRequest};
+use futures::{Stream, StreamExt, TryStreamExt};
+use gcloud_sdk::google::firestore::v1::{Write, WriteRequest, WriteResponse};
 use rsb_derive::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{mpsc, watch, RwLock, Semaphore};
 use tokio::task::JoinHandle;
 use tonic::Code;
 
@@ -23,19 +29,162 @@ use tracing::*;
 pub struct FirestoreStreamingBatchWriteOptions {
     #[default = "Duration::from_millis(500)"]
     pub throttle_batch_duration: Duration,
+    /// The number of batches buffered before sending them to Firestore.
+    /// Writing batches waits when the buffer is full. Unbounded if not specified.
+    pub requests_buffer_size: Option<usize>,
+    /// The number of responses buffered when they aren't consumed fast enough. Unbounded if not specified.
+    /// Acknowledgements of the batches don't wait for the responses to be consumed:
+    /// the responses exceeding the buffer are dropped and reported with an overflow error.
+    pub responses_buffer_size: Option<usize>,
+    /// The number of batches sent but not acknowledged by Firestore yet.
+    /// Writing batches waits when the limit is reached. Unlimited if not specified.
+    pub max_in_flight_batches: Option<usize>,
+    /// The number of consecutive failed attempts to reconnect the write stream,
+    /// after which the writer stops with the `Failed` status.
+    #[default = "5"]
+    pub max_reconnect_attempts: u32,
+    #[default = "Duration::from_millis(500)"]
+    pub initial_reconnect_interval: Duration,
+    #[default = "Duration::from_secs(30)"]
+    pub max_reconnect_interval: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FirestoreStreamingBatchWriterStatus {
+    Active,
+    Reconnecting(u32),
+    Finished,
+    Failed(String),
+}
+
+enum FirestoreStreamingChannelSender<T> {
+    Bounded(mpsc::Sender<T>),
+    Unbounded(UnboundedSender<T>),
+}
+
+impl<T> FirestoreStreamingChannelSender<T> {
+    async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        match self {
+            FirestoreStreamingChannelSender::Bounded(sender) => sender.send(value).await,
+            FirestoreStreamingChannelSender::Unbounded(sender) => sender.send(value),
+        }
+    }
+
+    fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        match self {
+            FirestoreStreamingChannelSender::Bounded(sender) => sender.try_send(value),
+            FirestoreStreamingChannelSender::Unbounded(sender) => sender
+                .send(value)
+                .map_err(|SendError(value)| TrySendError::Closed(value)),
+        }
+    }
 }
 
+// Responses are sent without waiting for them to be consumed,
+// so acknowledging the batches isn't blocked by a caller that doesn't read the responses.
+// Responses exceeding the buffer are dropped, and the number of dropped responses
+// is reported with an error as soon as the buffer has space again.
+struct FirestoreStreamingResponsesWriter {
+    sender: FirestoreStreamingChannelSender<FirestoreResult<FirestoreBatchWriteResponse>>,
+    dropped_responses: u64,
+}
+
+impl FirestoreStreamingResponsesWriter {
+    fn new(
+        sender: FirestoreStreamingChannelSender<FirestoreResult<FirestoreBatchWriteResponse>>,
+    ) -> Self {
+        Self {
+            sender,
+            dropped_responses: 0,
+        }
+    }
+
+    fn send(&mut self, response: FirestoreResult<FirestoreBatchWriteResponse>) {
+        if self.dropped_responses > 0 {
+            match self
+                .sender
+                .try_send(Err(responses_overflow_error(self.dropped_responses)))
+            {
+                Ok(()) => self.dropped_responses = 0,
+                Err(TrySendError::Full(_)) => {
+                    self.dropped_responses += 1;
+                    return;
+                }
+                Err(TrySendError::Closed(_)) => return,
+            }
+        }
+
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(response) {
+            warn!("Batch write responses buffer is full. Dropping the response");
+            self.dropped_responses += 1;
+        }
+    }
+}
+
+fn responses_overflow_error(dropped_responses: u64) -> FirestoreError {
+    FirestoreError::SystemError(FirestoreSystemError::new(
+        FirestoreErrorPublicGenericDetails::new("ResponsesBufferOverflow".into()),
+        format!(
+            "{} batch write responses have been dropped because the responses buffer was full",
+            dropped_responses
+        ),
+    ))
+}
+
+fn streaming_channel<T>(
+    buffer_size: Option<usize>,
+) -> (
+    FirestoreStreamingChannelSender<T>,
+    futures::future::Either<
+        tokio_stream::wrappers::ReceiverStream<T>,
+        tokio_stream::wrappers::UnboundedReceiverStream<T>,
+    >,
+)
+where
+    T: Send + 'static,
+{
+    match buffer_size {
+        Some(buffer_size) => {
+            let (sender, receiver) = mpsc::channel(buffer_size);
+            (
+                FirestoreStreamingChannelSender::Bounded(sender),
+                tokio_stream::wrappers::ReceiverStream::new(receiver).left_stream(),
+            )
+        }
+        None => {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            (
+                FirestoreStreamingChannelSender::Unbounded(sender),
+                tokio_stream::wrappers::UnboundedReceiverStream::new(receiver).right_stream(),
+            )
+        }
+    }
+}
+
+enum FirestoreStreamingBatchWriteCommand {
+    Write(u64, Vec<Write>),
+    Finish,
+}
+
+/// Writes batches using the streaming Write RPC.
+/// When the stream fails with a retryable error, the writer reconnects resuming the stream if possible,
+/// and sends again the batches following the last one acknowledged by the server.
+/// The stream is resumed only when the server continues right after the last response received by the writer.
+/// Otherwise a new stream is opened and all batches without received responses are sent again:
+/// those batches may be applied twice, so non-idempotent writes (such as increments) may be affected.
+/// Writing a batch returns its position, so the handles of the batch can be matched
+/// with `FirestoreBatchWriteResponse::handle_results` of the response with the same position.
 pub struct FirestoreStreamingBatchWriter {
     pub db: FirestoreDb,
     pub options: FirestoreStreamingBatchWriteOptions,
     pub batch_span: Span,
     finished: Arc<AtomicBool>,
-    writer: UnboundedSender<WriteRequest>,
+    writer: FirestoreStreamingChannelSender<FirestoreStreamingBatchWriteCommand>,
     thread: Option<JoinHandle<()>>,
-    last_token: Arc<RwLock<Vec<u8>>>,
-    sent_counter: Arc<AtomicU64>,
-    received_counter: Arc<AtomicU64>,
-    init_wait_reader: UnboundedReceiver<()>,
+    sent_counter: AtomicU64,
+    in_flight_batches: Option<Arc<Semaphore>>,
+    acknowledged_reader: watch::Receiver<u64>,
+    status: Arc<RwLock<FirestoreStreamingBatchWriterStatus>>,
 }
 
 impl Drop for FirestoreStreamingBatchWriter {
@@ -56,199 +205,66 @@ impl FirestoreStreamingBatchWriter {
     )> {
         let batch_span = span!(Level::DEBUG, "Firestore Batch Write");
 
-        let (requests_writer, requests_receiver) = mpsc::unbounded_channel::<WriteRequest>();
-        let (responses_writer, responses_receiver) =
-            mpsc::unbounded_channel::<FirestoreResult<FirestoreBatchWriteResponse>>();
-        let (init_wait_sender, mut init_wait_reader) = mpsc::unbounded_channel::<()>();
-
-        let finished = Arc::new(AtomicBool::new(false));
-        let thread_finished = finished.clone();
-
-        let sent_counter = Arc::new(AtomicU64::new(0));
-        let thread_sent_counter = sent_counter.clone();
-
-        let received_counter = Arc::new(AtomicU64::new(0));
-        let thread_received_counter = received_counter.clone();
-
-        let last_token: Arc<RwLock<Vec<u8>>> = Arc::new(RwLock::new(vec![]));
-        let thread_last_token = last_token.clone();
-
-        let mut thread_db_client = db.client().get();
-        let thread_options = options.clone();
-
-        let thread = tokio::spawn(async move {
-            let stream = {
-                use tokio_stream::StreamExt;
-                tokio_stream::wrappers::UnboundedReceiverStream::new(requests_receiver)
-                    .throttle(thread_options.throttle_batch_duration)
-            };
-            match thread_db_client.write(stream).await {
-                Ok(response) => {
-                    let mut response_stream = response.into_inner().boxed();
-                    loop {
-                        let response_result = response_stream.try_next().await;
-                        let received_counter = thread_received_counter.load(Ordering::Relaxed);
-
-                        match response_result {
-                            Ok(Some(response)) => {
-                                {
-                                    let mut locked = thread_last_token.write().await;
-                                    *locked = response.stream_token;
-                                }
-
-                                if received_counter == 0 {
-                                    init_wait_sender.send(()).ok();
-                                } else {
-                                    let write_results: FirestoreResult<Vec<FirestoreWriteResult>> =
-                                        response
-                                            .write_results
-                                            .into_iter()
-                                            .map(|s| s.try_into())
-                                            .collect();
-
-                                    match write_results {
-                                        Ok(write_results) => {
-                                            responses_writer
-                                                .send(Ok(FirestoreBatchWriteResponse::new(
-                                                    received_counter - 1,
-                                                    write_results,
-                                                    vec![],
-                                                )
-                                                .opt_commit_time(
-                                                    response
-                                                        .commit_time
-                                                        .and_then(|ts| from_timestamp(ts).ok()),
-                                                )))
-                                                .ok();
-                                        }
-                                        Err(err) => {
-                                            error!(
-                                                "Batch write operation {} failed: {}",
-                                                received_counter, err
-                                            );
-                                            responses_writer.send(Err(err)).ok();
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                            Ok(None) => {
-                                responses_writer
-                                    .send(Ok(FirestoreBatchWriteResponse::new(
-                                        received_counter - 1,
-                                        vec![],
-                                        vec![],
-                                    )))
-                                    .ok();
-                                break;
-                            }
-                            Err(err) if err.code() == Code::Cancelled => {
-                                debug!("Batch write operation finished on: {}", received_counter);
-                                responses_writer
-                                    .send(Ok(FirestoreBatchWriteResponse::new(
-                                        received_counter - 1,
-                                        vec![],
-                                        vec![],
-                                    )))
-                                    .ok();
-                                break;
-                            }
-                            Err(err) => {
-                                error!(
-                                    "Batch write operation {} failed: {}",
-                                    received_counter, err
-                                );
-                                responses_writer.send(Err(err.into())).ok();
-                                break;
-                            }
-                        }
-
-                        {
-                            let _locked = thread_last_token.read().await;
-                            if thread_finished.load(Ordering::Relaxed)
-                                && thread_sent_counter.load(Ordering::Relaxed) == received_counter
-                            {
-                                init_wait_sender.send(()).ok();
-                                break;
-                            }
-                        }
-
-                        thread_received_counter.fetch_add(1, Ordering::Relaxed);
-                    }
-
-                    {
-                        let _locked = thread_last_token.write().await;
-                        thread_finished.store(true, Ordering::Relaxed);
-                        init_wait_sender.send(()).ok();
-                    }
-                }
-                Err(err) => {
-                    error!("Batch write operation failed: {}", err);
-                    responses_writer.send(Err(err.into())).ok();
-                }
-            }
-        });
-
-        requests_writer.send(WriteRequest {
-            database: db.get_database_path().to_string(),
-            stream_id: "".to_string(),
-            writes: vec![],
+        let (commands_writer, commands_stream) =
+            streaming_channel::<FirestoreStreamingBatchWriteCommand>(options.requests_buffer_size);
+        let (responses_writer, responses_stream) = streaming_channel::<
+            FirestoreResult<FirestoreBatchWriteResponse>,
+        >(options.responses_buffer_size);
+
+        let in_flight_batches = options
+            .max_in_flight_batches
+            .map(|max_in_flight_batches| Arc::new(Semaphore::new(max_in_flight_batches)));
+
+        let (acknowledged_writer, acknowledged_reader) = watch::channel(0u64);
+
+        let status = Arc::new(RwLock::new(FirestoreStreamingBatchWriterStatus::Active));
+
+        let mut stream_state = FirestoreStreamingBatchWriteState {
+            db: db.clone(),
+            options: options.clone(),
+            batch_span: batch_span.clone(),
+            responses_writer: FirestoreStreamingResponsesWriter::new(responses_writer),
+            acknowledged_writer,
+            in_flight_batches: in_flight_batches.clone(),
+            status: status.clone(),
+            stream_id: String::new(),
             stream_token: vec![],
-            labels: HashMap::new(),
-        })?;
+            unacknowledged_batches: VecDeque::new(),
+            acknowledged: 0,
+        };
 
-        init_wait_reader.recv().await;
+        let connection = stream_state.connect().await?;
 
-        let responses_stream =
-            tokio_stream::wrappers::UnboundedReceiverStream::new(responses_receiver).boxed();
+        let commands_stream = {
+            use tokio_stream::StreamExt;
+            commands_stream.throttle(options.throttle_batch_duration)
+        };
+
+        let thread = tokio::spawn(stream_state.run(connection, commands_stream));
 
         Ok((
             Self {
                 db,
                 options,
                 batch_span,
-                finished,
-                writer: requests_writer,
+                finished: Arc::new(AtomicBool::new(false)),
+                writer: commands_writer,
                 thread: Some(thread),
-                last_token,
-                sent_counter,
-                received_counter,
-                init_wait_reader,
+                sent_counter: AtomicU64::new(0),
+                in_flight_batches,
+                acknowledged_reader,
+                status,
             },
-            responses_stream,
+            responses_stream.boxed(),
         ))
     }
 
     pub async fn finish(mut self) {
-        let locked = self.last_token.write().await;
-
-        if !self.finished.load(Ordering::Relaxed) {
-            self.finished.store(true, Ordering::Relaxed);
-
-            if self.sent_counter.load(Ordering::Relaxed)
-                > self.received_counter.load(Ordering::Relaxed) - 1
-            {
-                drop(locked);
-                debug!("Still waiting receiving responses for batch writes");
-                self.init_wait_reader.recv().await;
-            } else {
-                drop(locked);
-            }
-
+        if !self.finished.swap(true, Ordering::Relaxed) {
             self.writer
-                .send(WriteRequest {
-                    database: self.db.get_database_path().to_string(),
-                    stream_id: "".to_string(),
-                    writes: vec![],
-                    stream_token: {
-                        let locked = self.last_token.read().await;
-                        locked.clone()
-                    },
-                    labels: HashMap::new(),
-                })
+                .send(FirestoreStreamingBatchWriteCommand::Finish)
+                .await
                 .ok();
-        } else {
-            drop(locked);
         }
 
         if let Some(thread) = self.thread.take() {
@@ -256,23 +272,68 @@ impl FirestoreStreamingBatchWriter {
         }
     }
 
-    async fn write_iterator<I>(&self, writes: I) -> FirestoreResult<()>
+    /// The current status of the writer, including terminal failures
+    pub async fn status(&self) -> FirestoreStreamingBatchWriterStatus {
+        self.status.read().await.clone()
+    }
+
+    /// Waits until all the batches sent so far are acknowledged by Firestore.
+    /// Acknowledgements don't depend on consuming the responses stream.
+    pub async fn flush(&self) -> FirestoreResult<()> {
+        let sent = self.sent_counter.load(Ordering::Relaxed);
+        let mut acknowledged_reader = self.acknowledged_reader.clone();
+
+        while *acknowledged_reader.borrow() < sent {
+            if acknowledged_reader.changed().await.is_err() {
+                return Err(self.stream_closed_error().await);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn write_iterator<I>(&self, writes: I) -> FirestoreResult<u64>
     where
         I: IntoIterator,
         I::Item: Into<Write>,
     {
-        self.sent_counter.fetch_add(1, Ordering::Relaxed);
+        if let FirestoreStreamingBatchWriterStatus::Failed(_) = *self.status.read().await {
+            return Err(self.stream_closed_error().await);
+        }
 
-        Ok(self.writer.send(WriteRequest {
-            database: self.db.get_database_path().to_string(),
-            stream_id: "".to_string(),
-            writes: writes.into_iter().map(|write| write.into()).collect(),
-            stream_token: {
-                let locked = self.last_token.read().await;
-                locked.clone()
-            },
-            labels: HashMap::new(),
-        })?)
+        if let Some(ref in_flight_batches) = self.in_flight_batches {
+            acquire_in_flight_batch(in_flight_batches).await?;
+        }
+
+        let position = self.sent_counter.fetch_add(1, Ordering::Relaxed);
+
+        if self
+            .writer
+            .send(FirestoreStreamingBatchWriteCommand::Write(
+                position,
+                writes.into_iter().map(|write| write.into()).collect(),
+            ))
+            .await
+            .is_err()
+        {
+            return Err(self.stream_closed_error().await);
+        }
+
+        Ok(position)
+    }
+
+    async fn stream_closed_error(&self) -> FirestoreError {
+        let message = match *self.status.read().await {
+            FirestoreStreamingBatchWriterStatus::Failed(ref reason) => {
+                format!("Batch write stream failed permanently: {}", reason)
+            }
+            _ => "Batch write stream closed before acknowledging all batches".to_string(),
+        };
+
+        FirestoreError::NetworkError(FirestoreNetworkError::new(
+            FirestoreErrorPublicGenericDetails::new("STREAM_CLOSED".into()),
+            message,
+        ))
     }
 
     pub fn new_batch(&self) -> FirestoreBatch<FirestoreStreamingBatchWriter> {
@@ -282,13 +343,349 @@ impl FirestoreStreamingBatchWriter {
 
 #[async_trait]
 impl FirestoreBatchWriter for FirestoreStreamingBatchWriter {
-    type WriteResult = ();
-
-    async fn write(&self, writes: Vec<Write>) -> FirestoreResult<()> {
+    /// The position of the batch, matching `FirestoreBatchWriteResponse::position`
+    /// of its response in the responses stream.
+    type WriteResult = u64;
+
+    /// Writes in a streaming request are applied atomically,
+    /// so the batches exceeding the limits are rejected before sending them.
+    async fn write(&self, writes: Vec<Write>) -> FirestoreResult<u64> {
+        check_batch_writes_limits(&writes)?;
         self.write_iterator(writes).await
     }
 }
 
+// Permits are returned on acknowledging the batches,
+// and the semaphore is closed when the stream stops so that no writes wait forever for them
+async fn acquire_in_flight_batch(in_flight_batches: &Semaphore) -> FirestoreResult<()> {
+    in_flight_batches
+        .acquire()
+        .await
+        .map_err(|err| {
+            FirestoreError::SystemError(FirestoreSystemError::new(
+                FirestoreErrorPublicGenericDetails::new("SystemError".into()),
+                format!("Unable to acquire an in-flight batch permit: {}", err),
+            ))
+        })?
+        .forget();
+    Ok(())
+}
+
+fn failed_batch_response(
+    position: u64,
+    writes_count: usize,
+    status: &tonic::Status,
+) -> FirestoreBatchWriteResponse {
+    let mut write_results = Vec::with_capacity(writes_count);
+    let mut statuses = Vec::with_capacity(writes_count);
+    add_failed_request_results(&mut write_results, &mut statuses, writes_count, status);
+    FirestoreBatchWriteResponse::new(position, write_results, statuses)
+}
+
+type FirestoreStreamingBatchWriteConnection = (
+    UnboundedSender<WriteRequest>,
+    BoxStream<'static, Result<WriteResponse, tonic::Status>>,
+);
+
+struct FirestoreStreamingBatchWriteState {
+    db: FirestoreDb,
+    options: FirestoreStreamingBatchWriteOptions,
+    batch_span: Span,
+    responses_writer: FirestoreStreamingResponsesWriter,
+    acknowledged_writer: watch::Sender<u64>,
+    in_flight_batches: Option<Arc<Semaphore>>,
+    status: Arc<RwLock<FirestoreStreamingBatchWriterStatus>>,
+    stream_id: String,
+    stream_token: Vec<u8>,
+    unacknowledged_batches: VecDeque<(u64, Vec<Write>)>,
+    acknowledged: u64,
+}
+
+impl FirestoreStreamingBatchWriteState {
+    async fn run<C>(mut self, connection: FirestoreStreamingBatchWriteConnection, commands: C)
+    where
+        C: Stream<Item = FirestoreStreamingBatchWriteCommand> + Send,
+    {
+        let (mut requests_writer, mut response_stream) = connection;
+        let mut commands = Box::pin(commands);
+        let mut finishing = false;
+
+        loop {
+            if finishing && self.unacknowledged_batches.is_empty() {
+                // Acknowledging the last responses before closing the stream
+                requests_writer.send(self.create_request(vec![])).ok();
+                *self.status.write().await = FirestoreStreamingBatchWriterStatus::Finished;
+                break;
+            }
+
+            tokio::select! {
+                command = commands.next(), if !finishing => match command {
+                    Some(FirestoreStreamingBatchWriteCommand::Write(position, writes)) => {
+                        // Failures of the closed stream are handled on receiving the responses
+                        requests_writer.send(self.create_request(writes.clone())).ok();
+                        self.unacknowledged_batches.push_back((position, writes));
+                    }
+                    Some(FirestoreStreamingBatchWriteCommand::Finish) | None => {
+                        finishing = true;
+                    }
+                },
+                response = response_stream.try_next() => {
+                    let err: FirestoreError = match response {
+                        Ok(Some(response)) => {
+                            self.apply_response(response).await;
+                            continue;
+                        }
+                        Ok(None) => FirestoreError::DatabaseError(FirestoreDatabaseError::new(
+                            FirestoreErrorPublicGenericDetails::new("STREAM_CLOSED".into()),
+                            "Batch write stream was closed by the server".into(),
+                            true,
+                        )),
+                        Err(status) if status.code() == Code::Cancelled && finishing => {
+                            debug!("Batch write stream was cancelled while finishing");
+                            if !self.unacknowledged_batches.is_empty() {
+                                self.fail(&status, status.clone().into()).await;
+                            }
+                            break;
+                        }
+                        Err(status) => status.into(),
+                    };
+
+                    match self.reconnect(err).await {
+                        Some((new_requests_writer, new_response_stream)) => {
+                            requests_writer = new_requests_writer;
+                            response_stream = new_response_stream;
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        if let Some(ref in_flight_batches) = self.in_flight_batches {
+            in_flight_batches.close();
+        }
+    }
+
+    fn create_request(&self, writes: Vec<Write>) -> WriteRequest {
+        WriteRequest {
+            database: self.db.get_database_path().to_string(),
+            stream_id: "".to_string(),
+            writes,
+            stream_token: self.stream_token.clone(),
+            labels: HashMap::new(),
+        }
+    }
+
+    async fn apply_response(&mut self, response: WriteResponse) {
+        self.stream_token = response.stream_token;
+
+        let position = match self.unacknowledged_batches.pop_front() {
+            Some((position, _)) => position,
+            None => {
+                self.batch_span
+                    .in_scope(|| warn!("Received a response without any unacknowledged batches"));
+                return;
+            }
+        };
+
+        self.acknowledged += 1;
+        self.acknowledged_writer.send(self.acknowledged).ok();
+        if let Some(ref in_flight_batches) = self.in_flight_batches {
+            in_flight_batches.add_permits(1);
+        }
+
+        let write_results: FirestoreResult<Vec<FirestoreWriteResult>> = response
+            .write_results
+            .into_iter()
+            .map(|s| s.try_into())
+            .collect();
+
+        let batch_response = write_results.map(|write_results| {
+            FirestoreBatchWriteResponse::new(position, write_results, vec![])
+                .opt_commit_time(response.commit_time.and_then(|ts| from_timestamp(ts).ok()))
+        });
+
+        if let Err(ref err) = batch_response {
+            self.batch_span
+                .in_scope(|| error!("Batch write operation {} failed: {}", position, err));
+        }
+
+        self.responses_writer.send(batch_response);
+    }
+
+    // Opens the write stream resuming the previous one when possible.
+    // A resumed stream continues after the last acknowledged stream token of the server,
+    // so it's used only when the server hasn't acknowledged any batches without received responses:
+    // then all unacknowledged batches follow that token and can be sent again without duplicates.
+    async fn connect(&mut self) -> FirestoreResult<FirestoreStreamingBatchWriteConnection> {
+        if !self.stream_id.is_empty() {
+            let last_received_token = self.stream_token.clone();
+            match self.open_stream().await {
+                Ok(connection) if self.stream_token == last_received_token => {
+                    return Ok(connection)
+                }
+                Ok(_) => {
+                    self.batch_span.in_scope(|| {
+                        debug!(
+                            "Batch write stream was resumed after responses which haven't been received. Opening a new stream"
+                        )
+                    });
+                }
+                Err(err) => {
+                    self.batch_span.in_scope(|| {
+                        debug!(
+                            "Unable to resume the batch write stream: {}. Opening a new stream",
+                            err
+                        )
+                    });
+                }
+            }
+            self.stream_id = String::new();
+            self.stream_token = vec![];
+        }
+
+        self.open_stream().await
+    }
+
+    async fn open_stream(&mut self) -> FirestoreResult<FirestoreStreamingBatchWriteConnection> {
+        let (requests_writer, requests_receiver) = mpsc::unbounded_channel::<WriteRequest>();
+
+        requests_writer.send(WriteRequest {
+            database: self.db.get_database_path().to_string(),
+            stream_id: self.stream_id.clone(),
+            writes: vec![],
+            stream_token: self.stream_token.clone(),
+            labels: HashMap::new(),
+        })?;
+
+        let request =
+            self.db
+                .create_request(tokio_stream::wrappers::UnboundedReceiverStream::new(
+                    requests_receiver,
+                ))?;
+
+        let mut response_stream = self
+            .db
+            .client()
+            .get()
+            .write(request)
+            .await?
+            .into_inner()
+            .boxed();
+
+        match response_stream.try_next().await? {
+            Some(handshake_response) => {
+                self.stream_id = handshake_response.stream_id;
+                self.stream_token = handshake_response.stream_token;
+                Ok((requests_writer, response_stream))
+            }
+            None => Err(FirestoreError::DatabaseError(FirestoreDatabaseError::new(
+                FirestoreErrorPublicGenericDetails::new("STREAM_CLOSED".into()),
+                "Batch write stream was closed before the handshake".into(),
+                true,
+            ))),
+        }
+    }
+
+    async fn reconnect(
+        &mut self,
+        err: FirestoreError,
+    ) -> Option<FirestoreStreamingBatchWriteConnection> {
+        let mut backoff = ExponentialBackoffBuilder::new()
+            .with_initial_interval(self.options.initial_reconnect_interval)
+            .with_max_interval(self.options.max_reconnect_interval)
+            .with_max_elapsed_time(None)
+            .build();
+
+        let mut last_err = err;
+        let mut attempts: u32 = 0;
+
+        while Self::is_retryable_error(&last_err) && attempts < self.options.max_reconnect_attempts
+        {
+            attempts += 1;
+            *self.status.write().await =
+                FirestoreStreamingBatchWriterStatus::Reconnecting(attempts);
+
+            let delay = backoff
+                .next_backoff()
+                .unwrap_or(self.options.max_reconnect_interval);
+
+            self.batch_span.in_scope(|| {
+                warn!(
+                    "Batch write stream failed: {}. Reconnecting in {:?}. Attempt: {}",
+                    last_err, delay, attempts
+                )
+            });
+
+            tokio::time::sleep(delay).await;
+
+            match self.connect().await {
+                Ok((requests_writer, response_stream)) => {
+                    // On a resumed stream the unacknowledged batches are exactly the batches following
+                    // the last acknowledged stream token, and on a new stream all of them are sent again
+                    self.batch_span.in_scope(|| {
+                        debug!(
+                            "Batch write stream reconnected. Sending {} unacknowledged batches again",
+                            self.unacknowledged_batches.len()
+                        )
+                    });
+
+                    for (_, writes) in self.unacknowledged_batches.iter() {
+                        requests_writer
+                            .send(self.create_request(writes.clone()))
+                            .ok();
+                    }
+
+                    *self.status.write().await = FirestoreStreamingBatchWriterStatus::Active;
+                    return Some((requests_writer, response_stream));
+                }
+                Err(err) => {
+                    last_err = err;
+                }
+            }
+        }
+
+        self.batch_span.in_scope(|| {
+            error!(
+                "Batch write stream failed permanently after {} reconnect attempts: {}",
+                attempts, last_err
+            )
+        });
+
+        // The outcome of the batches without responses is unknown
+        let status = tonic::Status::unknown(last_err.to_string());
+        self.fail(&status, last_err).await;
+        None
+    }
+
+    // Stops the writer reporting the batches without responses as failed,
+    // so every batch written gets a response
+    async fn fail(&mut self, status: &tonic::Status, err: FirestoreError) {
+        *self.status.write().await = FirestoreStreamingBatchWriterStatus::Failed(err.to_string());
+
+        for (position, writes) in std::mem::take(&mut self.unacknowledged_batches) {
+            self.batch_span.in_scope(|| {
+                error!(
+                    "Batch write operation {} failed without a response: {}",
+                    position, err
+                )
+            });
+            self.responses_writer
+                .send(Ok(failed_batch_response(position, writes.len(), status)));
+        }
+
+        self.responses_writer.send(Err(err));
+    }
+
+    fn is_retryable_error(err: &FirestoreError) -> bool {
+        match err {
+            FirestoreError::DatabaseError(ref db_err) => db_err.retry_possible,
+            FirestoreError::NetworkError(_) => true,
+            _ => false,
+        }
+    }
+}
+
 impl FirestoreDb {
     pub async fn create_streaming_batch_writer<'a, 'b>(
         &'a self,
@@ -310,3 +707,77 @@ impl FirestoreDb {
         FirestoreStreamingBatchWriter::new(self.clone(), options).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_only_on_retryable_errors() {
+        let unavailable: FirestoreError = tonic::Status::unavailable("unavailable").into();
+        assert!(FirestoreStreamingBatchWriteState::is_retryable_error(
+            &unavailable
+        ));
+
+        let permission_denied: FirestoreError =
+            tonic::Status::permission_denied("permission denied").into();
+        assert!(!FirestoreStreamingBatchWriteState::is_retryable_error(
+            &permission_denied
+        ));
+    }
+
+    #[tokio::test]
+    async fn in_flight_batches_fail_after_closing() {
+        let in_flight_batches = Arc::new(Semaphore::new(1));
+        assert!(acquire_in_flight_batch(&in_flight_batches).await.is_ok());
+
+        let waiting = tokio::spawn({
+            let in_flight_batches = in_flight_batches.clone();
+            async move { acquire_in_flight_batch(&in_flight_batches).await }
+        });
+        tokio::task::yield_now().await;
+        in_flight_batches.close();
+
+        assert!(matches!(
+            waiting.await.unwrap(),
+            Err(FirestoreError::SystemError(_))
+        ));
+    }
+
+    #[test]
+    fn responses_writer_reports_dropped_responses() {
+        let (sender, mut receiver) =
+            streaming_channel::<FirestoreResult<FirestoreBatchWriteResponse>>(Some(1));
+        let mut responses_writer = FirestoreStreamingResponsesWriter::new(sender);
+        let response = |position| Ok(FirestoreBatchWriteResponse::new(position, vec![], vec![]));
+
+        responses_writer.send(response(0));
+        // Doesn't wait for the full buffer
+        responses_writer.send(response(1));
+        responses_writer.send(response(2));
+        assert_eq!(responses_writer.dropped_responses, 2);
+
+        let received = futures::executor::block_on(receiver.next());
+        assert!(matches!(received, Some(Ok(ref response)) if response.position == 0));
+
+        responses_writer.send(response(3));
+        let received = futures::executor::block_on(receiver.next());
+        assert!(matches!(
+            received,
+            Some(Err(FirestoreError::SystemError(ref err))) if err.message.starts_with("2 batch write responses")
+        ));
+        assert_eq!(responses_writer.dropped_responses, 1);
+    }
+
+    #[test]
+    fn failed_batch_response_fails_every_write() {
+        let response = failed_batch_response(7, 2, &tonic::Status::cancelled("cancelled"));
+
+        assert_eq!(response.position, 7);
+        assert_eq!(response.write_results.len(), 2);
+        assert!(response
+            .statuses
+            .iter()
+            .all(|status| status.code == Code::Cancelled as i32 && status.message == "cancelled"));
+    }
+}
@@ -1,17 +1,27 @@
-use crate::db::transaction_ops::{TransformObjectOperation, UpdateObjectOperation};
+use crate::db::transaction_ops::{
+    diff_update_params, is_create_write, write_document_path, CreateObjectOperation,
+    TransformObjectOperation, UpdateObjectOperation,
+};
 use crate::db::DeleteOperation;
-use crate::errors::FirestoreError;
+use crate::errors::*;
 use crate::{
-    FirestoreDb, FirestoreFieldTransform, FirestoreResult, FirestoreWritePrecondition,
-    FirestoreWriteResult,
+    generate_document_id, FirestoreDb, FirestoreFieldTransform, FirestoreResult, FirestoreValue,
+    FirestoreWritePrecondition, FirestoreWriteResult,
 };
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use gcloud_sdk::google::firestore::v1::Write;
 use gcloud_sdk::google::rpc::Status;
+use prost::Message;
 use rsb_derive::*;
 use serde::Serialize;
 
+/// The maximum number of writes in a single batch write request.
+pub const FIRESTORE_BATCH_MAX_WRITES: usize = 500;
+
+/// The maximum size of a single batch write request in bytes.
+pub const FIRESTORE_BATCH_MAX_REQUEST_SIZE: usize = 10 * 1024 * 1024;
+
 #[async_trait]
 pub trait FirestoreBatchWriter {
     type WriteResult;
@@ -19,6 +29,73 @@ pub trait FirestoreBatchWriter {
     async fn write(&self, writes: Vec<Write>) -> FirestoreResult<Self::WriteResult>;
 }
 
+pub(crate) fn estimate_write_size(write: &Write) -> usize {
+    let write_len = write.encoded_len();
+    // The field tag and the length delimiter of the repeated field in the request
+    1 + prost::length_delimiter_len(write_len) + write_len
+}
+
+/// Splits writes into chunks within the batch limits, for the writers which don't apply batches atomically.
+pub(crate) fn split_batch_writes(writes: Vec<Write>) -> FirestoreResult<Vec<Vec<Write>>> {
+    let mut chunks: Vec<Vec<Write>> = Vec::new();
+    let mut current_chunk: Vec<Write> = Vec::new();
+    let mut current_chunk_size: usize = 0;
+
+    for write in writes {
+        let write_size = estimate_write_size(&write);
+        if write_size > FIRESTORE_BATCH_MAX_REQUEST_SIZE {
+            return Err(batch_limits_error(format!(
+                "A write of {} bytes exceeds the maximum batch request size of {} bytes",
+                write_size, FIRESTORE_BATCH_MAX_REQUEST_SIZE
+            )));
+        }
+
+        if !current_chunk.is_empty()
+            && (current_chunk.len() >= FIRESTORE_BATCH_MAX_WRITES
+                || current_chunk_size + write_size > FIRESTORE_BATCH_MAX_REQUEST_SIZE)
+        {
+            chunks.push(std::mem::take(&mut current_chunk));
+            current_chunk_size = 0;
+        }
+
+        current_chunk_size += write_size;
+        current_chunk.push(write);
+    }
+
+    if !current_chunk.is_empty() {
+        chunks.push(current_chunk);
+    }
+
+    Ok(chunks)
+}
+
+/// Checks writes against the batch limits, for the writers applying batches atomically.
+pub(crate) fn check_batch_writes_limits(writes: &[Write]) -> FirestoreResult<()> {
+    if writes.len() > FIRESTORE_BATCH_MAX_WRITES {
+        return Err(batch_limits_error(format!(
+            "The batch contains {} writes, but the maximum is {}",
+            writes.len(),
+            FIRESTORE_BATCH_MAX_WRITES
+        )));
+    }
+
+    let request_size: usize = writes.iter().map(estimate_write_size).sum();
+    if request_size > FIRESTORE_BATCH_MAX_REQUEST_SIZE {
+        return Err(batch_limits_error(format!(
+            "The batch request size is about {} bytes, but the maximum is {} bytes",
+            request_size, FIRESTORE_BATCH_MAX_REQUEST_SIZE
+        )));
+    }
+
+    Ok(())
+}
+
+fn batch_limits_error(message: String) -> FirestoreError {
+    FirestoreError::InvalidParametersError(FirestoreInvalidParametersError::new(
+        FirestoreInvalidParametersPublicDetails::new("writes".into(), message),
+    ))
+}
+
 #[derive(Debug, PartialEq, Clone, Builder)]
 pub struct FirestoreBatchWriteResponse {
     pub position: u64,
@@ -27,6 +104,107 @@ pub struct FirestoreBatchWriteResponse {
     pub commit_time: Option<DateTime<Utc>>,
 }
 
+impl FirestoreBatchWriteResponse {
+    /// The result of the write identified by the handle,
+    /// or `None` if the response doesn't contain it.
+    pub fn handle_result(
+        &self,
+        handle: &FirestoreBatchWriteHandle,
+    ) -> Option<FirestoreBatchWriteHandleResult> {
+        let write_result = self.write_results.get(handle.index);
+        let status = self.statuses.get(handle.index).cloned();
+
+        if write_result.is_none() && status.is_none() {
+            return None;
+        }
+
+        Some(FirestoreBatchWriteHandleResult {
+            handle: handle.clone(),
+            update_time: write_result.and_then(|write_result| write_result.update_time),
+            transform_results: write_result
+                .map(|write_result| write_result.transform_results.clone())
+                .unwrap_or_default(),
+            status,
+        })
+    }
+
+    /// The results of the writes identified by the handles,
+    /// skipping the handles which the response doesn't contain.
+    pub fn handle_results(
+        &self,
+        handles: &[FirestoreBatchWriteHandle],
+    ) -> Vec<FirestoreBatchWriteHandleResult> {
+        handles
+            .iter()
+            .filter_map(|handle| self.handle_result(handle))
+            .collect()
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
+pub enum FirestoreBatchWriteOperation {
+    Create,
+    Update,
+    Delete,
+    Transform,
+}
+
+/// Identifies a write in a batch to correlate it with its result after the batch is written.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct FirestoreBatchWriteHandle {
+    /// The position of the write in the batch.
+    pub index: usize,
+    pub document_path: String,
+    pub operation: FirestoreBatchWriteOperation,
+}
+
+impl FirestoreBatchWriteHandle {
+    pub(crate) fn from_write(index: usize, write: &Write) -> Option<Self> {
+        let operation = match write.operation {
+            Some(gcloud_sdk::google::firestore::v1::write::Operation::Update(_))
+                if is_create_write(write) =>
+            {
+                FirestoreBatchWriteOperation::Create
+            }
+            Some(gcloud_sdk::google::firestore::v1::write::Operation::Update(_)) => {
+                FirestoreBatchWriteOperation::Update
+            }
+            Some(gcloud_sdk::google::firestore::v1::write::Operation::Delete(_)) => {
+                FirestoreBatchWriteOperation::Delete
+            }
+            Some(gcloud_sdk::google::firestore::v1::write::Operation::Transform(_)) => {
+                FirestoreBatchWriteOperation::Transform
+            }
+            None => return None,
+        };
+
+        Some(Self {
+            index,
+            document_path: write_document_path(write)?.to_string(),
+            operation,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FirestoreBatchWriteHandleResult {
+    pub handle: FirestoreBatchWriteHandle,
+    pub update_time: Option<DateTime<Utc>>,
+    pub transform_results: Vec<FirestoreValue>,
+    /// The status of the write when the writer reports statuses for individual writes.
+    /// `None` for the writers applying batches atomically, where a response means all writes were applied.
+    pub status: Option<Status>,
+}
+
+impl FirestoreBatchWriteHandleResult {
+    pub fn is_success(&self) -> bool {
+        self.status
+            .as_ref()
+            .map(|status| status.code == tonic::Code::Ok as i32)
+            .unwrap_or(true)
+    }
+}
+
 pub struct FirestoreBatch<'a, W>
 where
     W: FirestoreBatchWriter,
@@ -62,6 +240,72 @@ where
         self.writer.write(self.writes).await
     }
 
+    /// Handles of the writes added to the batch so far, in the order of the writes.
+    pub fn handles(&self) -> Vec<FirestoreBatchWriteHandle> {
+        self.writes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, write)| FirestoreBatchWriteHandle::from_write(index, write))
+            .collect()
+    }
+
+    /// Handle of the last write added to the batch.
+    pub fn last_handle(&self) -> Option<FirestoreBatchWriteHandle> {
+        self.writes
+            .last()
+            .and_then(|write| FirestoreBatchWriteHandle::from_write(self.writes.len() - 1, write))
+    }
+
+    /// Estimated size of the writes in the request in bytes.
+    pub fn estimated_request_size(&self) -> usize {
+        self.writes.iter().map(estimate_write_size).sum()
+    }
+
+    /// Creates a document failing the write if it already exists.
+    /// Document ids are generated on the client when not specified.
+    /// Returns the id of the document, including the generated one.
+    pub fn create_object<T, S>(
+        &mut self,
+        collection_id: &str,
+        document_id: Option<S>,
+        obj: &T,
+    ) -> FirestoreResult<String>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        self.create_object_at(
+            self.db.get_documents_path(),
+            collection_id,
+            document_id,
+            obj,
+        )
+    }
+
+    pub fn create_object_at<T, S>(
+        &mut self,
+        parent: &str,
+        collection_id: &str,
+        document_id: Option<S>,
+        obj: &T,
+    ) -> FirestoreResult<String>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        let document_id = document_id
+            .map(|document_id| document_id.as_ref().to_string())
+            .unwrap_or_else(generate_document_id);
+
+        self.add(CreateObjectOperation {
+            parent: parent.to_string(),
+            collection_id: collection_id.to_string(),
+            document_id: Some(document_id.as_str()),
+            obj,
+        })?;
+        Ok(document_id)
+    }
+
     pub fn update_object<T, S>(
         &mut self,
         collection_id: &str,
@@ -111,6 +355,60 @@ where
         })
     }
 
+    /// Updates only the fields that differ between the `old` and `new` versions of an object.
+    /// Nothing is written when the versions are equal and there are no transforms.
+    pub fn update_object_diff<T, S>(
+        &mut self,
+        collection_id: &str,
+        document_id: S,
+        old: &T,
+        new: &T,
+        precondition: Option<FirestoreWritePrecondition>,
+        update_transforms: Vec<FirestoreFieldTransform>,
+    ) -> FirestoreResult<&mut Self>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        self.update_object_diff_at(
+            self.db.get_documents_path(),
+            collection_id,
+            document_id,
+            old,
+            new,
+            precondition,
+            update_transforms,
+        )
+    }
+
+    pub fn update_object_diff_at<T, S>(
+        &mut self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+        old: &T,
+        new: &T,
+        precondition: Option<FirestoreWritePrecondition>,
+        update_transforms: Vec<FirestoreFieldTransform>,
+    ) -> FirestoreResult<&mut Self>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        match diff_update_params(old, new, precondition, !update_transforms.is_empty())? {
+            Some((update_only, precondition)) => self.update_object_at(
+                parent,
+                collection_id,
+                document_id,
+                new,
+                Some(update_only),
+                precondition,
+                update_transforms,
+            ),
+            None => Ok(self),
+        }
+    }
+
     pub fn delete_by_id<S>(
         &mut self,
         collection_id: &str,
@@ -185,3 +483,124 @@ where
         })
     }
 }
+
+impl<'a, W> FirestoreBatch<'a, W>
+where
+    W: FirestoreBatchWriter<WriteResult = FirestoreBatchWriteResponse>,
+{
+    /// Writes the batch and returns the result for every write in the batch.
+    pub async fn write_with_handle_results(
+        self,
+    ) -> FirestoreResult<Vec<FirestoreBatchWriteHandleResult>> {
+        let handles = self.handles();
+        let response = self.write().await?;
+        Ok(response.handle_results(&handles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delete_write(idx: usize) -> Write {
+        Write {
+            update_mask: None,
+            update_transforms: vec![],
+            current_document: None,
+            operation: Some(gcloud_sdk::google::firestore::v1::write::Operation::Delete(
+                format!(
+                    "projects/test/databases/(default)/documents/test/doc-{}",
+                    idx
+                ),
+            )),
+        }
+    }
+
+    #[test]
+    fn split_batch_writes_by_count() {
+        let writes: Vec<Write> = (0..1201).map(delete_write).collect();
+
+        assert!(check_batch_writes_limits(&writes).is_err());
+        assert!(check_batch_writes_limits(&writes[0..500]).is_ok());
+
+        let chunks = split_batch_writes(writes).unwrap();
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(),
+            vec![500, 500, 201]
+        );
+    }
+
+    #[test]
+    fn split_batch_writes_by_size() {
+        let large_write = || Write {
+            update_mask: None,
+            update_transforms: vec![],
+            current_document: None,
+            operation: Some(gcloud_sdk::google::firestore::v1::write::Operation::Delete(
+                "x".repeat(4 * 1024 * 1024),
+            )),
+        };
+        let writes = vec![large_write(), large_write(), large_write()];
+
+        assert!(check_batch_writes_limits(&writes).is_err());
+
+        let chunks = split_batch_writes(writes).unwrap();
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+    }
+
+    #[test]
+    fn correlate_handle_results() {
+        let create_write = Write {
+            current_document: Some(gcloud_sdk::google::firestore::v1::Precondition {
+                condition_type: Some(
+                    gcloud_sdk::google::firestore::v1::precondition::ConditionType::Exists(false),
+                ),
+            }),
+            operation: Some(gcloud_sdk::google::firestore::v1::write::Operation::Update(
+                gcloud_sdk::google::firestore::v1::Document {
+                    name: "projects/test/databases/(default)/documents/test/doc-new".into(),
+                    ..Default::default()
+                },
+            )),
+            ..delete_write(0)
+        };
+        let writes = vec![delete_write(0), create_write];
+
+        let handles: Vec<FirestoreBatchWriteHandle> = writes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, write)| FirestoreBatchWriteHandle::from_write(index, write))
+            .collect();
+
+        assert_eq!(handles[0].operation, FirestoreBatchWriteOperation::Delete);
+        assert_eq!(handles[1].operation, FirestoreBatchWriteOperation::Create);
+        assert_eq!(
+            handles[1].document_path,
+            "projects/test/databases/(default)/documents/test/doc-new"
+        );
+
+        let response = FirestoreBatchWriteResponse::new(
+            0,
+            vec![
+                FirestoreWriteResult::new(vec![]),
+                FirestoreWriteResult::new(vec![]),
+            ],
+            vec![
+                Status::default(),
+                Status {
+                    code: tonic::Code::AlreadyExists as i32,
+                    ..Default::default()
+                },
+            ],
+        );
+
+        let results = response.handle_results(&handles);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_success());
+        assert!(!results[1].is_success());
+        assert_eq!(results[1].handle, handles[1]);
+    }
+}
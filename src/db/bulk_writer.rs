@@ -0,0 +1,938 @@
+use crate::db::batch_writer::estimate_write_size;
+use crate::db::transaction_ops::{
+    write_document_path, CreateObjectOperation, DeleteOperation, TransformObjectOperation,
+    UpdateObjectOperation,
+};
+use crate::errors::*;
+use crate::{
+    FirestoreDb, FirestoreFieldTransform, FirestoreResult, FirestoreWritePrecondition,
+    FirestoreWriteResult, FIRESTORE_BATCH_MAX_REQUEST_SIZE, FIRESTORE_BATCH_MAX_WRITES,
+};
+use backoff::backoff::Backoff;
+use backoff::{ExponentialBackoff, ExponentialBackoffBuilder};
+use gcloud_sdk::google::firestore::v1::{BatchWriteRequest, Write};
+use rsb_derive::*;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::*;
+
+/// Options for the bulk writer.
+/// The writer follows the 500/50/5 rule: it starts with `initial_ops_per_second`
+/// and increases the rate by `ramp_up_multiplier` every `ramp_up_interval`.
+/// Writes failed with retryable statuses are retried with exponential backoff up to `max_attempts` times.
+#[derive(Debug, Clone, Builder)]
+pub struct FirestoreBulkWriterOptions {
+    /// Maximum number of writes in a BatchWrite request, up to `FIRESTORE_BATCH_MAX_WRITES`.
+    #[default = "FIRESTORE_BATCH_MAX_WRITES"]
+    pub max_batch_size: usize,
+    #[default = "500"]
+    pub initial_ops_per_second: u32,
+    pub max_ops_per_second: Option<u32>,
+    #[default = "1.5"]
+    pub ramp_up_multiplier: f64,
+    #[default = "Duration::from_secs(5 * 60)"]
+    pub ramp_up_interval: Duration,
+    #[default = "10"]
+    pub max_attempts: u32,
+    #[default = "Duration::from_secs(1)"]
+    pub initial_retry_interval: Duration,
+    #[default = "Duration::from_secs(60)"]
+    pub max_retry_interval: Duration,
+}
+
+impl FirestoreBulkWriterOptions {
+    pub fn validate(&self) -> FirestoreResult<()> {
+        if self.max_batch_size == 0 || self.max_batch_size > FIRESTORE_BATCH_MAX_WRITES {
+            Err(FirestoreError::InvalidParametersError(
+                FirestoreInvalidParametersError::new(FirestoreInvalidParametersPublicDetails::new(
+                    "max_batch_size".to_string(),
+                    format!(
+                        "Batch size {} is out of the allowed range from 1 to {}",
+                        self.max_batch_size, FIRESTORE_BATCH_MAX_WRITES
+                    ),
+                )),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Writes documents in non-atomic batches in the background.
+/// Each write gets a handle resolving to its final outcome after all retries.
+pub struct FirestoreBulkWriter {
+    pub db: FirestoreDb,
+    pub options: FirestoreBulkWriterOptions,
+    pub bulk_span: Span,
+    commands_writer: UnboundedSender<FirestoreBulkWriterCommand>,
+    thread: JoinHandle<()>,
+}
+
+/// Resolves to the result of the write when it is either applied or failed permanently.
+pub struct FirestoreBulkWriteHandle {
+    receiver: oneshot::Receiver<FirestoreResult<FirestoreWriteResult>>,
+}
+
+impl Future for FirestoreBulkWriteHandle {
+    type Output = FirestoreResult<FirestoreWriteResult>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(|received| {
+            received.unwrap_or_else(|_| {
+                Err(bulk_writer_stopped_error(
+                    "Bulk writer stopped before the write was processed",
+                ))
+            })
+        })
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+enum FirestoreBulkWriterCommand {
+    Write(FirestoreBulkWriterPendingWrite),
+    Flush(oneshot::Sender<()>),
+}
+
+struct FirestoreBulkWriterPendingWrite {
+    write: Write,
+    attempts: u32,
+    backoff: Option<ExponentialBackoff>,
+    retry_at: Option<Instant>,
+    result_sender: oneshot::Sender<FirestoreResult<FirestoreWriteResult>>,
+}
+
+impl FirestoreBulkWriter {
+    pub async fn new(
+        db: FirestoreDb,
+        options: FirestoreBulkWriterOptions,
+    ) -> FirestoreResult<FirestoreBulkWriter> {
+        options.validate()?;
+
+        let bulk_span = span!(Level::DEBUG, "Firestore Bulk Write");
+
+        let (commands_writer, commands_receiver) = mpsc::unbounded_channel();
+
+        let thread = tokio::spawn(Self::process_writes(
+            db.clone(),
+            options.clone(),
+            commands_receiver,
+            bulk_span.clone(),
+        ));
+
+        Ok(Self {
+            db,
+            options,
+            bulk_span,
+            commands_writer,
+            thread,
+        })
+    }
+
+    pub fn write<I>(&self, write: I) -> FirestoreResult<FirestoreBulkWriteHandle>
+    where
+        I: TryInto<Write, Error = FirestoreError>,
+    {
+        let (result_sender, receiver) = oneshot::channel();
+
+        self.commands_writer
+            .send(FirestoreBulkWriterCommand::Write(
+                FirestoreBulkWriterPendingWrite {
+                    write: write.try_into()?,
+                    attempts: 0,
+                    backoff: None,
+                    retry_at: None,
+                    result_sender,
+                },
+            ))
+            .map_err(|_| bulk_writer_stopped_error("Bulk writer is already closed"))?;
+
+        Ok(FirestoreBulkWriteHandle { receiver })
+    }
+
+    /// Waits until all the writes submitted so far are either applied or failed permanently.
+    pub async fn flush(&self) -> FirestoreResult<()> {
+        let (flush_sender, flush_receiver) = oneshot::channel();
+        self.commands_writer
+            .send(FirestoreBulkWriterCommand::Flush(flush_sender))
+            .map_err(|_| bulk_writer_stopped_error("Bulk writer is already closed"))?;
+        flush_receiver
+            .await
+            .map_err(|_| bulk_writer_stopped_error("Bulk writer stopped before flushing"))
+    }
+
+    pub async fn close(self) -> FirestoreResult<()> {
+        self.flush().await?;
+        drop(self.commands_writer);
+        self.thread.await.ok();
+        self.bulk_span
+            .in_scope(|| debug!("Bulk writer has been closed"));
+        Ok(())
+    }
+
+    pub fn create_object<T, S>(
+        &self,
+        collection_id: &str,
+        document_id: Option<S>,
+        obj: &T,
+    ) -> FirestoreResult<FirestoreBulkWriteHandle>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        self.create_object_at(
+            self.db.get_documents_path(),
+            collection_id,
+            document_id,
+            obj,
+        )
+    }
+
+    pub fn create_object_at<T, S>(
+        &self,
+        parent: &str,
+        collection_id: &str,
+        document_id: Option<S>,
+        obj: &T,
+    ) -> FirestoreResult<FirestoreBulkWriteHandle>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        self.write(CreateObjectOperation {
+            parent: parent.to_string(),
+            collection_id: collection_id.to_string(),
+            document_id,
+            obj,
+        })
+    }
+
+    pub fn update_object<T, S>(
+        &self,
+        collection_id: &str,
+        document_id: S,
+        obj: &T,
+        update_only: Option<Vec<String>>,
+        precondition: Option<FirestoreWritePrecondition>,
+        update_transforms: Vec<FirestoreFieldTransform>,
+    ) -> FirestoreResult<FirestoreBulkWriteHandle>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        self.update_object_at(
+            self.db.get_documents_path(),
+            collection_id,
+            document_id,
+            obj,
+            update_only,
+            precondition,
+            update_transforms,
+        )
+    }
+
+    pub fn update_object_at<T, S>(
+        &self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+        obj: &T,
+        update_only: Option<Vec<String>>,
+        precondition: Option<FirestoreWritePrecondition>,
+        update_transforms: Vec<FirestoreFieldTransform>,
+    ) -> FirestoreResult<FirestoreBulkWriteHandle>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        self.write(UpdateObjectOperation {
+            parent: parent.to_string(),
+            collection_id: collection_id.to_string(),
+            document_id,
+            obj,
+            update_only,
+            precondition,
+            update_transforms,
+        })
+    }
+
+    pub fn delete_by_id<S>(
+        &self,
+        collection_id: &str,
+        document_id: S,
+        precondition: Option<FirestoreWritePrecondition>,
+    ) -> FirestoreResult<FirestoreBulkWriteHandle>
+    where
+        S: AsRef<str>,
+    {
+        self.delete_by_id_at(
+            self.db.get_documents_path(),
+            collection_id,
+            document_id,
+            precondition,
+        )
+    }
+
+    pub fn delete_by_id_at<S>(
+        &self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+        precondition: Option<FirestoreWritePrecondition>,
+    ) -> FirestoreResult<FirestoreBulkWriteHandle>
+    where
+        S: AsRef<str>,
+    {
+        self.write(DeleteOperation {
+            parent: parent.to_string(),
+            collection_id: collection_id.to_string(),
+            document_id,
+            precondition,
+        })
+    }
+
+    pub fn transform<S>(
+        &self,
+        collection_id: &str,
+        document_id: S,
+        precondition: Option<FirestoreWritePrecondition>,
+        transforms: Vec<FirestoreFieldTransform>,
+    ) -> FirestoreResult<FirestoreBulkWriteHandle>
+    where
+        S: AsRef<str>,
+    {
+        self.transform_at(
+            self.db.get_documents_path(),
+            collection_id,
+            document_id,
+            precondition,
+            transforms,
+        )
+    }
+
+    pub fn transform_at<S>(
+        &self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+        precondition: Option<FirestoreWritePrecondition>,
+        transforms: Vec<FirestoreFieldTransform>,
+    ) -> FirestoreResult<FirestoreBulkWriteHandle>
+    where
+        S: AsRef<str>,
+    {
+        self.write(TransformObjectOperation {
+            parent: parent.to_string(),
+            collection_id: collection_id.to_string(),
+            document_id,
+            precondition,
+            transforms,
+        })
+    }
+
+    async fn process_writes(
+        db: FirestoreDb,
+        options: FirestoreBulkWriterOptions,
+        mut commands_receiver: UnboundedReceiver<FirestoreBulkWriterCommand>,
+        bulk_span: Span,
+    ) {
+        let mut pending_writes = FirestoreBulkWriterQueue::new();
+        let mut flush_waiters: Vec<oneshot::Sender<()>> = Vec::new();
+        let mut rate_limiter = FirestoreBulkWriterRateLimiter::new(&options);
+        let mut commands_closed = false;
+
+        loop {
+            while !commands_closed {
+                match commands_receiver.try_recv() {
+                    Ok(command) => {
+                        Self::apply_command(command, &mut pending_writes, &mut flush_waiters)
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => commands_closed = true,
+                }
+            }
+
+            if pending_writes.is_empty() {
+                for flush_waiter in flush_waiters.drain(..) {
+                    flush_waiter.send(()).ok();
+                }
+
+                if commands_closed {
+                    break;
+                }
+
+                match commands_receiver.recv().await {
+                    Some(command) => {
+                        Self::apply_command(command, &mut pending_writes, &mut flush_waiters)
+                    }
+                    None => commands_closed = true,
+                }
+                continue;
+            }
+
+            let batch = pending_writes.take_ready_batch(options.max_batch_size, Instant::now());
+
+            if batch.is_empty() {
+                // Only the writes waiting for retries are left
+                let next_retry_at = pending_writes.next_retry_at().unwrap_or_else(Instant::now);
+
+                if commands_closed {
+                    tokio::time::sleep_until(next_retry_at.into()).await;
+                } else {
+                    tokio::select! {
+                        command = commands_receiver.recv() => match command {
+                            Some(command) => {
+                                Self::apply_command(command, &mut pending_writes, &mut flush_waiters)
+                            }
+                            None => commands_closed = true,
+                        },
+                        _ = tokio::time::sleep_until(next_retry_at.into()) => {}
+                    }
+                }
+                continue;
+            }
+
+            rate_limiter.acquire(batch.len()).await;
+
+            let batch_documents: Vec<String> = batch
+                .iter()
+                .map(|pending_write| pending_write.document_key())
+                .collect();
+            let retry_writes = Self::write_batch(&db, &options, batch, &bulk_span).await;
+            pending_writes.return_batch(batch_documents, retry_writes);
+        }
+    }
+
+    fn apply_command(
+        command: FirestoreBulkWriterCommand,
+        pending_writes: &mut FirestoreBulkWriterQueue,
+        flush_waiters: &mut Vec<oneshot::Sender<()>>,
+    ) {
+        match command {
+            FirestoreBulkWriterCommand::Write(pending_write) => {
+                pending_writes.push_back(pending_write)
+            }
+            FirestoreBulkWriterCommand::Flush(flush_waiter) => flush_waiters.push(flush_waiter),
+        }
+    }
+
+    async fn write_batch(
+        db: &FirestoreDb,
+        options: &FirestoreBulkWriterOptions,
+        mut batch: Vec<FirestoreBulkWriterPendingWrite>,
+        bulk_span: &Span,
+    ) -> Vec<FirestoreBulkWriterPendingWrite> {
+        for pending_write in batch.iter_mut() {
+            pending_write.attempts += 1;
+        }
+
+        let batch_len = batch.len();
+
+        let response = match db.create_request(BatchWriteRequest {
+            database: db.get_database_path().to_string(),
+            writes: batch
+                .iter()
+                .map(|pending_write| pending_write.write.clone())
+                .collect(),
+            labels: HashMap::new(),
+        }) {
+            Ok(request) => db
+                .client()
+                .get()
+                .batch_write(request)
+                .await
+                .map(|response| response.into_inner())
+                .map_err(FirestoreError::from),
+            Err(err) => Err(err),
+        };
+
+        match response {
+            Ok(response) => {
+                let mut write_results = response.write_results.into_iter();
+                let mut statuses = response.status.into_iter();
+
+                let retry_writes: Vec<FirestoreBulkWriterPendingWrite> = batch
+                    .into_iter()
+                    .filter_map(|pending_write| {
+                        let write_result = write_results.next();
+                        match statuses.next() {
+                            Some(status) if status.code != tonic::Code::Ok as i32 => {
+                                let err: FirestoreError = tonic::Status::new(
+                                    tonic::Code::from(status.code),
+                                    status.message,
+                                )
+                                .into();
+                                Self::retry_or_fail(pending_write, err, options)
+                            }
+                            _ => {
+                                let result = write_result
+                                    .ok_or_else(|| {
+                                        FirestoreError::SystemError(FirestoreSystemError::new(
+                                            FirestoreErrorPublicGenericDetails::new(
+                                                "SystemError".into(),
+                                            ),
+                                            "Missing write result in the batch write response"
+                                                .into(),
+                                        ))
+                                    })
+                                    .and_then(|write_result| write_result.try_into());
+                                pending_write.result_sender.send(result).ok();
+                                None
+                            }
+                        }
+                    })
+                    .collect();
+
+                bulk_span.in_scope(|| {
+                    debug!(
+                        "Bulk writer sent a batch of {} writes. Retrying: {}",
+                        batch_len,
+                        retry_writes.len()
+                    )
+                });
+
+                retry_writes
+            }
+            Err(err) => {
+                bulk_span.in_scope(|| {
+                    warn!(
+                        "Bulk writer failed to send a batch of {} writes: {}",
+                        batch_len, err
+                    )
+                });
+
+                batch
+                    .into_iter()
+                    .filter_map(|pending_write| {
+                        Self::retry_or_fail(pending_write, err.clone(), options)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn retry_or_fail(
+        mut pending_write: FirestoreBulkWriterPendingWrite,
+        err: FirestoreError,
+        options: &FirestoreBulkWriterOptions,
+    ) -> Option<FirestoreBulkWriterPendingWrite> {
+        if Self::is_retryable_error(&err) && pending_write.attempts < options.max_attempts {
+            let retry_delay = pending_write
+                .backoff
+                .get_or_insert_with(|| {
+                    ExponentialBackoffBuilder::new()
+                        .with_initial_interval(options.initial_retry_interval)
+                        .with_max_interval(options.max_retry_interval)
+                        .with_max_elapsed_time(None)
+                        .build()
+                })
+                .next_backoff();
+
+            if let Some(retry_delay) = retry_delay {
+                pending_write.retry_at = Some(Instant::now() + retry_delay);
+                return Some(pending_write);
+            }
+        }
+
+        pending_write.result_sender.send(Err(err)).ok();
+        None
+    }
+
+    fn is_retryable_error(err: &FirestoreError) -> bool {
+        matches!(err, FirestoreError::DatabaseError(ref db_err) if db_err.retry_possible)
+    }
+}
+
+impl FirestoreBulkWriterPendingWrite {
+    fn document_key(&self) -> String {
+        write_document_path(&self.write)
+            .map(|document_path| document_path.to_string())
+            .unwrap_or_default()
+    }
+}
+
+// BatchWrite doesn't allow more than one write per document in the same request,
+// so the writes are queued per document and only the first write of every document is sent.
+// The next write to a document is available only after the previous one has been written,
+// which keeps the order of the writes to every document, including the retried ones.
+struct FirestoreBulkWriterQueue {
+    documents: HashMap<String, VecDeque<FirestoreBulkWriterPendingWrite>>,
+    // Documents with the first write ready to be sent, in the order of the writes
+    ready_documents: VecDeque<String>,
+    // Documents with the first write waiting for a retry
+    delayed_documents: BinaryHeap<Reverse<(Instant, String)>>,
+    len: usize,
+}
+
+impl FirestoreBulkWriterQueue {
+    fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            ready_documents: VecDeque::new(),
+            delayed_documents: BinaryHeap::new(),
+            len: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn next_retry_at(&self) -> Option<Instant> {
+        self.delayed_documents
+            .peek()
+            .map(|Reverse((retry_at, _))| *retry_at)
+    }
+
+    fn push_back(&mut self, pending_write: FirestoreBulkWriterPendingWrite) {
+        self.len += 1;
+        let document_key = pending_write.document_key();
+        match self.documents.get_mut(&document_key) {
+            // The document is already scheduled or being written
+            Some(document_writes) => document_writes.push_back(pending_write),
+            None => {
+                let retry_at = pending_write.retry_at;
+                self.documents
+                    .insert(document_key.clone(), VecDeque::from([pending_write]));
+                self.schedule(document_key, retry_at);
+            }
+        }
+    }
+
+    fn schedule(&mut self, document_key: String, retry_at: Option<Instant>) {
+        match retry_at {
+            Some(retry_at) => self
+                .delayed_documents
+                .push(Reverse((retry_at, document_key))),
+            None => self.ready_documents.push_back(document_key),
+        }
+    }
+
+    fn take_ready_batch(
+        &mut self,
+        max_batch_size: usize,
+        now: Instant,
+    ) -> Vec<FirestoreBulkWriterPendingWrite> {
+        while let Some(Reverse((retry_at, _))) = self.delayed_documents.peek() {
+            if *retry_at > now {
+                break;
+            }
+            if let Some(Reverse((_, document_key))) = self.delayed_documents.pop() {
+                self.ready_documents.push_back(document_key);
+            }
+        }
+
+        let mut batch = Vec::new();
+        let mut batch_request_size: usize = 0;
+
+        while batch.len() < max_batch_size {
+            let document_writes = match self
+                .ready_documents
+                .front()
+                .and_then(|document_key| self.documents.get_mut(document_key))
+            {
+                Some(document_writes) => document_writes,
+                None => break,
+            };
+
+            let write_size = document_writes
+                .front()
+                .map(|pending_write| estimate_write_size(&pending_write.write))
+                .unwrap_or_default();
+            if !batch.is_empty()
+                && batch_request_size + write_size > FIRESTORE_BATCH_MAX_REQUEST_SIZE
+            {
+                break;
+            }
+
+            if let Some(pending_write) = document_writes.pop_front() {
+                batch_request_size += write_size;
+                batch.push(pending_write);
+                self.len -= 1;
+            }
+            self.ready_documents.pop_front();
+        }
+
+        batch
+    }
+
+    // Puts the writes to retry back in front of the later writes to the same documents
+    // and schedules the next writes of the written documents
+    fn return_batch(
+        &mut self,
+        batch_documents: Vec<String>,
+        retry_writes: Vec<FirestoreBulkWriterPendingWrite>,
+    ) {
+        for retry_write in retry_writes {
+            self.len += 1;
+            self.documents
+                .entry(retry_write.document_key())
+                .or_default()
+                .push_front(retry_write);
+        }
+
+        for document_key in batch_documents {
+            match self
+                .documents
+                .get(&document_key)
+                .and_then(|document_writes| document_writes.front())
+            {
+                Some(pending_write) => {
+                    let retry_at = pending_write.retry_at;
+                    self.schedule(document_key, retry_at);
+                }
+                None => {
+                    self.documents.remove(&document_key);
+                }
+            }
+        }
+    }
+}
+
+fn bulk_writer_stopped_error(message: &str) -> FirestoreError {
+    FirestoreError::SystemError(FirestoreSystemError::new(
+        FirestoreErrorPublicGenericDetails::new("BulkWriterStopped".into()),
+        message.to_string(),
+    ))
+}
+
+struct FirestoreBulkWriterRateLimiter {
+    started: Instant,
+    next_available: Instant,
+    initial_ops_per_second: u32,
+    max_ops_per_second: Option<u32>,
+    ramp_up_multiplier: f64,
+    ramp_up_interval: Duration,
+}
+
+impl FirestoreBulkWriterRateLimiter {
+    fn new(options: &FirestoreBulkWriterOptions) -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            next_available: now,
+            initial_ops_per_second: options.initial_ops_per_second,
+            max_ops_per_second: options.max_ops_per_second,
+            ramp_up_multiplier: options.ramp_up_multiplier,
+            ramp_up_interval: options.ramp_up_interval,
+        }
+    }
+
+    fn ops_per_second(&self, now: Instant) -> f64 {
+        let ramp_ups = (now.duration_since(self.started).as_secs_f64()
+            / self.ramp_up_interval.as_secs_f64())
+        .floor();
+
+        let ops_per_second =
+            self.initial_ops_per_second as f64 * self.ramp_up_multiplier.powf(ramp_ups);
+
+        self.max_ops_per_second
+            .map(|max_ops_per_second| ops_per_second.min(max_ops_per_second as f64))
+            .unwrap_or(ops_per_second)
+            .max(1.0)
+    }
+
+    async fn acquire(&mut self, ops: usize) {
+        let now = Instant::now();
+        if self.next_available > now {
+            tokio::time::sleep_until(self.next_available.into()).await;
+        }
+
+        let available_at = self.next_available.max(now);
+        self.next_available =
+            available_at + Duration::from_secs_f64(ops as f64 / self.ops_per_second(available_at));
+    }
+}
+
+impl FirestoreDb {
+    pub async fn create_bulk_writer(&self) -> FirestoreResult<FirestoreBulkWriter> {
+        self.create_bulk_writer_with_options(FirestoreBulkWriterOptions::new())
+            .await
+    }
+
+    pub async fn create_bulk_writer_with_options(
+        &self,
+        options: FirestoreBulkWriterOptions,
+    ) -> FirestoreResult<FirestoreBulkWriter> {
+        FirestoreBulkWriter::new(self.clone(), options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_write(
+        document_path: &str,
+        retry_at: Option<Instant>,
+    ) -> FirestoreBulkWriterPendingWrite {
+        let (result_sender, _) = oneshot::channel();
+        FirestoreBulkWriterPendingWrite {
+            write: Write {
+                update_mask: None,
+                update_transforms: vec![],
+                current_document: None,
+                operation: Some(gcloud_sdk::google::firestore::v1::write::Operation::Delete(
+                    document_path.to_string(),
+                )),
+            },
+            attempts: 0,
+            backoff: None,
+            retry_at,
+            result_sender,
+        }
+    }
+
+    fn queue(pending_writes: Vec<FirestoreBulkWriterPendingWrite>) -> FirestoreBulkWriterQueue {
+        let mut queue = FirestoreBulkWriterQueue::new();
+        for pending_write in pending_writes {
+            queue.push_back(pending_write);
+        }
+        queue
+    }
+
+    fn batch_paths(batch: &[FirestoreBulkWriterPendingWrite]) -> Vec<&str> {
+        batch
+            .iter()
+            .filter_map(|pending_write| write_document_path(&pending_write.write))
+            .collect()
+    }
+
+    fn return_written_batch(
+        queue: &mut FirestoreBulkWriterQueue,
+        batch: Vec<FirestoreBulkWriterPendingWrite>,
+    ) {
+        queue.return_batch(
+            batch
+                .iter()
+                .map(|pending_write| pending_write.document_key())
+                .collect(),
+            vec![],
+        );
+    }
+
+    #[test]
+    fn take_ready_batch_skips_duplicates_and_delayed_writes() {
+        let now = Instant::now();
+        let mut pending_writes = queue(vec![
+            pending_write("docs/a", None),
+            pending_write("docs/a", None),
+            pending_write("docs/b", Some(now + Duration::from_secs(1))),
+            pending_write("docs/c", None),
+            pending_write("docs/d", None),
+        ]);
+
+        let batch = pending_writes.take_ready_batch(2, now);
+        assert_eq!(batch_paths(&batch), vec!["docs/a", "docs/c"]);
+        assert_eq!(pending_writes.len, 3);
+
+        // The next write to a document waits until the previous one is written
+        let batch = pending_writes.take_ready_batch(10, now);
+        assert_eq!(batch_paths(&batch), vec!["docs/d"]);
+        return_written_batch(&mut pending_writes, batch);
+        assert!(pending_writes.take_ready_batch(10, now).is_empty());
+    }
+
+    #[test]
+    fn take_ready_batch_keeps_order_after_delayed_writes() {
+        let now = Instant::now();
+        let mut pending_writes = queue(vec![
+            pending_write("docs/a", Some(now + Duration::from_secs(1))),
+            pending_write("docs/a", None),
+            pending_write("docs/b", None),
+            pending_write("docs/c", None),
+            pending_write("docs/c", None),
+        ]);
+
+        let batch = pending_writes.take_ready_batch(1, now);
+        assert_eq!(batch_paths(&batch), vec!["docs/b"]);
+        return_written_batch(&mut pending_writes, batch);
+
+        let batch = pending_writes.take_ready_batch(10, now);
+        assert_eq!(batch_paths(&batch), vec!["docs/c"]);
+        return_written_batch(&mut pending_writes, batch);
+
+        let batch = pending_writes.take_ready_batch(10, now);
+        assert_eq!(batch_paths(&batch), vec!["docs/c"]);
+        return_written_batch(&mut pending_writes, batch);
+
+        assert!(pending_writes.take_ready_batch(10, now).is_empty());
+        assert_eq!(
+            pending_writes.next_retry_at(),
+            Some(now + Duration::from_secs(1))
+        );
+
+        let later = now + Duration::from_secs(1);
+        let batch = pending_writes.take_ready_batch(10, later);
+        assert_eq!(batch_paths(&batch), vec!["docs/a"]);
+        assert!(batch[0].retry_at.is_some());
+        return_written_batch(&mut pending_writes, batch);
+
+        let batch = pending_writes.take_ready_batch(10, later);
+        assert_eq!(batch_paths(&batch), vec!["docs/a"]);
+        assert!(batch[0].retry_at.is_none());
+        return_written_batch(&mut pending_writes, batch);
+        assert!(pending_writes.is_empty());
+    }
+
+    #[test]
+    fn return_batch_puts_retries_before_later_writes() {
+        let now = Instant::now();
+        let mut pending_writes = queue(vec![
+            pending_write("docs/a", None),
+            pending_write("docs/a", None),
+        ]);
+
+        let mut batch = pending_writes.take_ready_batch(10, now);
+        assert_eq!(batch_paths(&batch), vec!["docs/a"]);
+
+        let mut retry_write = batch.remove(0);
+        let retry_at = now + Duration::from_secs(1);
+        retry_write.retry_at = Some(retry_at);
+        pending_writes.return_batch(vec!["docs/a".to_string()], vec![retry_write]);
+        assert_eq!(pending_writes.len, 2);
+
+        assert!(pending_writes.take_ready_batch(10, now).is_empty());
+        let batch = pending_writes.take_ready_batch(10, retry_at);
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].retry_at, Some(retry_at));
+    }
+
+    #[test]
+    fn bulk_writer_options_batch_size_limits() {
+        assert!(FirestoreBulkWriterOptions::new().validate().is_ok());
+        assert!(FirestoreBulkWriterOptions::new()
+            .with_max_batch_size(0)
+            .validate()
+            .is_err());
+        assert!(FirestoreBulkWriterOptions::new()
+            .with_max_batch_size(FIRESTORE_BATCH_MAX_WRITES + 1)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn rate_limiter_ramps_up() {
+        let options = FirestoreBulkWriterOptions::new().with_max_ops_per_second(1000);
+        let rate_limiter = FirestoreBulkWriterRateLimiter::new(&options);
+        let started = rate_limiter.started;
+
+        assert_eq!(rate_limiter.ops_per_second(started), 500.0);
+        assert_eq!(
+            rate_limiter.ops_per_second(started + Duration::from_secs(5 * 60)),
+            750.0
+        );
+        assert_eq!(
+            rate_limiter.ops_per_second(started + Duration::from_secs(15 * 60)),
+            1000.0
+        );
+    }
+}
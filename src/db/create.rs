@@ -5,6 +5,22 @@ use gcloud_sdk::google::firestore::v1::*;
 use serde::{Deserialize, Serialize};
 use tracing::*;
 
+const AUTO_DOCUMENT_ID_ALPHABET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const AUTO_DOCUMENT_ID_LEN: usize = 20;
+
+/// Generates a random document id on the client, the same way as the official Firestore SDKs.
+/// Useful when you need to know the id of a document created in a transaction or a batch.
+pub fn generate_document_id() -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    (0..AUTO_DOCUMENT_ID_LEN)
+        .map(|_| {
+            AUTO_DOCUMENT_ID_ALPHABET[rng.gen_range(0..AUTO_DOCUMENT_ID_ALPHABET.len())] as char
+        })
+        .collect()
+}
+
 #[async_trait]
 pub trait FirestoreCreateSupport {
     async fn create_doc<S>(
@@ -94,7 +110,7 @@ impl FirestoreCreateSupport for FirestoreDb {
             "/firestore/response_time" = field::Empty
         );
 
-        let create_document_request = tonic::Request::new(CreateDocumentRequest {
+        let create_document_request = self.create_request(CreateDocumentRequest {
             parent: parent.into(),
             document_id: document_id
                 .as_ref()
@@ -105,7 +121,7 @@ impl FirestoreCreateSupport for FirestoreDb {
             }),
             collection_id: collection_id.into(),
             document: Some(input_doc),
-        });
+        })?;
 
         let begin_query_utc: DateTime<Utc> = Utc::now();
 
@@ -1,10 +1,55 @@
 use crate::db::safe_document_path;
-use crate::{FirestoreDb, FirestoreResult, FirestoreWritePrecondition};
+use crate::db::transaction_ops::DeleteDocumentPathOperation;
+use crate::{
+    FirestoreBulkWriteHandle, FirestoreBulkWriter, FirestoreBulkWriterOptions, FirestoreDb,
+    FirestoreError, FirestoreListCollectionIdsParams, FirestoreListDocParams,
+    FirestoreListingSupport, FirestoreQueryParams, FirestoreQuerySupport, FirestoreResult,
+    FirestoreWritePrecondition, FirestoreWriteResult,
+};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, StreamExt, TryStreamExt};
 use gcloud_sdk::google::firestore::v1::*;
+use rsb_derive::*;
+use std::sync::Arc;
 use tracing::*;
 
+/// Counts of the documents processed by deleting by query or recursively.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct FirestoreDeleteProgress {
+    pub deleted: u64,
+    pub failed: u64,
+}
+
+pub type FirestoreDeleteProgressCallback = Arc<dyn Fn(&FirestoreDeleteProgress) + Send + Sync>;
+
+/// Options for deleting by query and recursively.
+/// The deletes are sent using a bulk writer created with `bulk_writer_options`.
+/// With `recursive` the subcollections of the deleted documents are deleted too.
+/// `on_progress` is called every time a delete is either applied or failed permanently.
+#[derive(Clone, Builder)]
+pub struct FirestoreDeleteOptions {
+    #[default = "false"]
+    pub recursive: bool,
+    pub bulk_writer_options: Option<FirestoreBulkWriterOptions>,
+    pub on_progress: Option<FirestoreDeleteProgressCallback>,
+}
+
+impl std::fmt::Debug for FirestoreDeleteOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FirestoreDeleteOptions")
+            .field("recursive", &self.recursive)
+            .field("bulk_writer_options", &self.bulk_writer_options)
+            .field(
+                "on_progress",
+                &self.on_progress.as_ref().map(|_| "<callback>"),
+            )
+            .finish()
+    }
+}
+
 #[async_trait]
 pub trait FirestoreDeleteSupport {
     async fn delete_by_id<S>(
@@ -25,6 +70,32 @@ pub trait FirestoreDeleteSupport {
     ) -> FirestoreResult<()>
     where
         S: AsRef<str> + Send;
+
+    /// Deletes all documents returned by the query.
+    /// The deletes are not atomic, and all of them are attempted even if some fail,
+    /// in which case the first error is returned.
+    async fn delete_by_query(
+        &self,
+        params: FirestoreQueryParams,
+    ) -> FirestoreResult<FirestoreDeleteProgress>;
+
+    async fn delete_by_query_with_options(
+        &self,
+        params: FirestoreQueryParams,
+        options: FirestoreDeleteOptions,
+    ) -> FirestoreResult<FirestoreDeleteProgress>;
+
+    /// Deletes the document and all documents in its subcollections at any depth.
+    async fn recursive_delete(
+        &self,
+        document_path: &str,
+    ) -> FirestoreResult<FirestoreDeleteProgress>;
+
+    async fn recursive_delete_with_options(
+        &self,
+        document_path: &str,
+        options: FirestoreDeleteOptions,
+    ) -> FirestoreResult<FirestoreDeleteProgress>;
 }
 
 #[async_trait]
@@ -66,10 +137,10 @@ impl FirestoreDeleteSupport for FirestoreDb {
             "/firestore/response_time" = field::Empty
         );
 
-        let request = tonic::Request::new(DeleteDocumentRequest {
+        let request = self.create_request(DeleteDocumentRequest {
             name: document_path,
             current_document: precondition.map(|cond| cond.try_into()).transpose()?,
-        });
+        })?;
 
         let begin_query_utc: DateTime<Utc> = Utc::now();
         self.client().get().delete_document(request).await?;
@@ -91,4 +162,191 @@ impl FirestoreDeleteSupport for FirestoreDb {
 
         Ok(())
     }
+
+    async fn delete_by_query(
+        &self,
+        params: FirestoreQueryParams,
+    ) -> FirestoreResult<FirestoreDeleteProgress> {
+        self.delete_by_query_with_options(params, FirestoreDeleteOptions::new())
+            .await
+    }
+
+    async fn delete_by_query_with_options(
+        &self,
+        params: FirestoreQueryParams,
+        options: FirestoreDeleteOptions,
+    ) -> FirestoreResult<FirestoreDeleteProgress> {
+        let mut runner = FirestoreDeleteRunner::new(self, options).await?;
+
+        let listed = async {
+            // Only document names are needed to delete them
+            let mut doc_stream = self
+                .stream_query_doc_with_errors(
+                    params.with_return_only_fields(vec!["__name__".to_string()]),
+                )
+                .await?;
+
+            while let Some(doc) = doc_stream.try_next().await? {
+                runner.delete_document(doc.name).await?;
+            }
+            Ok(())
+        }
+        .await;
+
+        runner.finish_after(listed).await
+    }
+
+    async fn recursive_delete(
+        &self,
+        document_path: &str,
+    ) -> FirestoreResult<FirestoreDeleteProgress> {
+        self.recursive_delete_with_options(
+            document_path,
+            FirestoreDeleteOptions::new().with_recursive(true),
+        )
+        .await
+    }
+
+    async fn recursive_delete_with_options(
+        &self,
+        document_path: &str,
+        options: FirestoreDeleteOptions,
+    ) -> FirestoreResult<FirestoreDeleteProgress> {
+        let mut runner = FirestoreDeleteRunner::new(self, options.with_recursive(true)).await?;
+        let listed = runner.delete_document(document_path.to_string()).await;
+        runner.finish_after(listed).await
+    }
+}
+
+struct FirestoreDeleteRunner<'a> {
+    db: &'a FirestoreDb,
+    options: FirestoreDeleteOptions,
+    bulk_writer: FirestoreBulkWriter,
+    pending_deletes: FuturesUnordered<FirestoreBulkWriteHandle>,
+    progress: FirestoreDeleteProgress,
+    first_error: Option<FirestoreError>,
+}
+
+impl<'a> FirestoreDeleteRunner<'a> {
+    async fn new(db: &'a FirestoreDb, options: FirestoreDeleteOptions) -> FirestoreResult<Self> {
+        let bulk_writer = db
+            .create_bulk_writer_with_options(
+                options
+                    .bulk_writer_options
+                    .clone()
+                    .unwrap_or_else(FirestoreBulkWriterOptions::new),
+            )
+            .await?;
+
+        Ok(Self {
+            db,
+            options,
+            bulk_writer,
+            pending_deletes: FuturesUnordered::new(),
+            progress: FirestoreDeleteProgress::default(),
+            first_error: None,
+        })
+    }
+
+    fn delete_document(&mut self, document_path: String) -> BoxFuture<'_, FirestoreResult<()>> {
+        async move {
+            if self.options.recursive {
+                let db = self.db;
+                let mut collection_ids = db
+                    .stream_list_collection_ids_with_errors(
+                        FirestoreListCollectionIdsParams::new().with_parent(document_path.clone()),
+                    )
+                    .await?;
+
+                while let Some(collection_id) = collection_ids.try_next().await? {
+                    self.delete_collection(document_path.clone(), collection_id)
+                        .await?;
+                }
+            }
+
+            self.pending_deletes.push(
+                self.bulk_writer
+                    .write(DeleteDocumentPathOperation { document_path })?,
+            );
+            self.collect_completed();
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn delete_collection(
+        &mut self,
+        parent: String,
+        collection_id: String,
+    ) -> BoxFuture<'_, FirestoreResult<()>> {
+        async move {
+            let db = self.db;
+            // Missing documents don't exist, but still may have subcollections to delete
+            let mut doc_stream = db
+                .stream_list_doc_with_errors(
+                    FirestoreListDocParams::new(collection_id)
+                        .with_parent(parent)
+                        .with_return_only_fields(vec![])
+                        .with_show_missing(true),
+                )
+                .await?;
+
+            while let Some(doc) = doc_stream.try_next().await? {
+                self.delete_document(doc.name).await?;
+            }
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn collect_completed(&mut self) {
+        while let Some(Some(result)) = self.pending_deletes.next().now_or_never() {
+            self.record_result(result);
+        }
+    }
+
+    fn record_result(&mut self, result: FirestoreResult<FirestoreWriteResult>) {
+        match result {
+            Ok(_) => self.progress.deleted += 1,
+            Err(err) => {
+                self.progress.failed += 1;
+                self.first_error.get_or_insert(err);
+            }
+        }
+
+        if let Some(ref on_progress) = self.options.on_progress {
+            on_progress(&self.progress);
+        }
+    }
+
+    // Waits for the deletes already sent and closes the bulk writer
+    // also when listing the documents to delete has failed, returning the listing error then
+    async fn finish_after(
+        self,
+        listed: FirestoreResult<()>,
+    ) -> FirestoreResult<FirestoreDeleteProgress> {
+        let finished = self.finish().await;
+        listed?;
+        finished
+    }
+
+    async fn finish(mut self) -> FirestoreResult<FirestoreDeleteProgress> {
+        while let Some(result) = self.pending_deletes.next().await {
+            self.record_result(result);
+        }
+
+        self.bulk_writer.close().await?;
+
+        debug!(
+            "[DB]: Deleted {} documents, failed to delete {} documents",
+            self.progress.deleted, self.progress.failed
+        );
+
+        match self.first_error {
+            Some(err) => Err(err),
+            None => Ok(self.progress),
+        }
+    }
 }
@@ -352,7 +352,7 @@ impl FirestoreGetByIdSupport for FirestoreDb {
             "/firestore/ids_count" = full_doc_ids.len()
         );
 
-        let request = tonic::Request::new(BatchGetDocumentsRequest {
+        let request = self.create_request(BatchGetDocumentsRequest {
             database: self.get_database_path().clone(),
             documents: full_doc_ids,
             consistency_selector: self
@@ -366,7 +366,7 @@ impl FirestoreGetByIdSupport for FirestoreDb {
                     field_paths: vf.iter().map(|f| f.to_string()).collect(),
                 }
             }),
-        });
+        })?;
         match self.client().get().batch_get_documents(request).await {
             Ok(response) => {
                 span.in_scope(|| debug!("Start consuming a batch of documents by ids"));
@@ -602,7 +602,7 @@ impl FirestoreDb {
         async move {
             let begin_query_utc: DateTime<Utc> = Utc::now();
 
-            let request = tonic::Request::new(GetDocumentRequest {
+            let request = self.create_request(GetDocumentRequest {
                 name: document_path.clone(),
                 consistency_selector: self
                     .session_params
@@ -615,7 +615,7 @@ impl FirestoreDb {
                         field_paths: vf.iter().map(|f| f.to_string()).collect(),
                     }
                 }),
-            });
+            })?;
 
             match self
                 .client()
@@ -5,7 +5,6 @@ use futures::future::BoxFuture;
 use futures::stream::BoxStream;
 use futures::FutureExt;
 use futures::StreamExt;
-use futures::TryFutureExt;
 use futures::TryStreamExt;
 use gcloud_sdk::google::firestore::v1::*;
 use rsb_derive::*;
@@ -25,6 +24,10 @@ pub struct FirestoreListDocParams {
     pub page_token: Option<String>,
     pub order_by: Option<Vec<FirestoreQueryOrder>>,
     pub return_only_fields: Option<Vec<String>>,
+
+    /// Include the missing documents which don't exist but have subcollections.
+    #[default = "false"]
+    pub show_missing: bool,
 }
 
 #[derive(Debug, PartialEq, Clone, Builder)]
@@ -33,6 +36,23 @@ pub struct FirestoreListDocResult {
     pub page_token: Option<String>,
 }
 
+#[derive(Debug, Eq, PartialEq, Clone, Builder)]
+pub struct FirestoreListCollectionIdsParams {
+    /// The parent document path. The root collections are listed if not specified.
+    pub parent: Option<String>,
+
+    #[default = "100"]
+    pub page_size: usize,
+
+    pub page_token: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Builder)]
+pub struct FirestoreListCollectionIdsResult {
+    pub collection_ids: Vec<String>,
+    pub page_token: Option<String>,
+}
+
 #[async_trait]
 pub trait FirestoreListingSupport {
     async fn list_doc(
@@ -63,6 +83,21 @@ pub trait FirestoreListingSupport {
     ) -> FirestoreResult<BoxStream<FirestoreResult<T>>>
     where
         for<'de> T: Deserialize<'de>;
+
+    async fn list_collection_ids(
+        &self,
+        params: FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<FirestoreListCollectionIdsResult>;
+
+    async fn stream_list_collection_ids(
+        &self,
+        params: FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<BoxStream<String>>;
+
+    async fn stream_list_collection_ids_with_errors(
+        &self,
+        params: FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<BoxStream<FirestoreResult<String>>>;
 }
 
 #[async_trait]
@@ -181,6 +216,90 @@ impl FirestoreListingSupport for FirestoreDb {
             Self::deserialize_doc_to::<T>(&doc)
         })))
     }
+
+    async fn list_collection_ids(
+        &self,
+        params: FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<FirestoreListCollectionIdsResult> {
+        let span = span!(
+            Level::DEBUG,
+            "Firestore ListCollectionIds",
+            "/firestore/response_time" = field::Empty
+        );
+
+        self.list_collection_ids_with_retries(params, 0, &span)
+            .await
+    }
+
+    async fn stream_list_collection_ids_with_errors(
+        &self,
+        params: FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<BoxStream<FirestoreResult<String>>> {
+        let stream: BoxStream<FirestoreResult<String>> = Box::pin(
+            futures::stream::unfold(Some(params), move |maybe_params| async move {
+                if let Some(params) = maybe_params {
+                    let span = span!(
+                        Level::DEBUG,
+                        "Firestore Streaming ListCollectionIds",
+                        "/firestore/response_time" = field::Empty
+                    );
+
+                    match self
+                        .list_collection_ids_with_retries(params.clone(), 0, &span)
+                        .await
+                    {
+                        Ok(results) => {
+                            if let Some(next_page_token) = results.page_token.clone() {
+                                Some((Ok(results), Some(params.with_page_token(next_page_token))))
+                            } else {
+                                Some((Ok(results), None))
+                            }
+                        }
+                        Err(err) => {
+                            error!(
+                                "[DB] Error occurred while consuming collection IDs: {}",
+                                err
+                            );
+                            Some((Err(err), None))
+                        }
+                    }
+                } else {
+                    None
+                }
+            })
+            .flat_map(|ids_res| {
+                futures::stream::iter(match ids_res {
+                    Ok(results) => results
+                        .collection_ids
+                        .into_iter()
+                        .map(Ok::<String, FirestoreError>)
+                        .collect(),
+                    Err(err) => vec![Err(err)],
+                })
+            }),
+        );
+
+        Ok(stream)
+    }
+
+    async fn stream_list_collection_ids(
+        &self,
+        params: FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<BoxStream<String>> {
+        let ids_stream = self.stream_list_collection_ids_with_errors(params).await?;
+        Ok(Box::pin(ids_stream.filter_map(|ids_res| {
+            future::ready(match ids_res {
+                Ok(collection_id) => Some(collection_id),
+                Err(err) => {
+                    error!(
+                        "[DB] Error occurred while consuming collection IDs: {}",
+                        err
+                    );
+                    None
+                }
+            })
+        })))
+    }
 }
 
 impl FirestoreDb {
@@ -188,7 +307,7 @@ impl FirestoreDb {
         &self,
         params: &FirestoreListDocParams,
     ) -> FirestoreResult<tonic::Request<ListDocumentsRequest>> {
-        Ok(tonic::Request::new(ListDocumentsRequest {
+        self.create_request(ListDocumentsRequest {
             parent: params
                 .parent
                 .as_ref()
@@ -220,8 +339,8 @@ impl FirestoreDb {
                 .as_ref()
                 .map(|selector| selector.try_into())
                 .transpose()?,
-            show_missing: false,
-        }))
+            show_missing: params.show_missing,
+        })
     }
 
     fn list_doc_with_retries<'a>(
@@ -230,26 +349,107 @@ impl FirestoreDb {
         retries: usize,
         span: &'a Span,
     ) -> BoxFuture<'a, FirestoreResult<FirestoreListDocResult>> {
+        let description = format!("Listing documents in {:?}", params.collection_id);
+        self.list_with_retries(
+            move || {
+                let list_request = self.create_list_request(&params);
+                async move {
+                    let list_inner = self
+                        .client
+                        .get()
+                        .list_documents(list_request?)
+                        .await?
+                        .into_inner();
+                    Ok(
+                        FirestoreListDocResult::new(list_inner.documents).opt_page_token(
+                            if !list_inner.next_page_token.is_empty() {
+                                Some(list_inner.next_page_token)
+                            } else {
+                                None
+                            },
+                        ),
+                    )
+                }
+                .boxed()
+            },
+            description,
+            retries,
+            span,
+        )
+    }
+
+    fn list_collection_ids_with_retries<'a>(
+        &'a self,
+        params: FirestoreListCollectionIdsParams,
+        retries: usize,
+        span: &'a Span,
+    ) -> BoxFuture<'a, FirestoreResult<FirestoreListCollectionIdsResult>> {
+        let description = format!("Listing collection IDs in {:?}", params.parent);
+        self.list_with_retries(
+            move || {
+                let list_request = self.create_list_collection_ids_request(&params);
+                async move {
+                    let list_inner = self
+                        .client
+                        .get()
+                        .list_collection_ids(list_request?)
+                        .await?
+                        .into_inner();
+                    Ok(
+                        FirestoreListCollectionIdsResult::new(list_inner.collection_ids)
+                            .opt_page_token(if !list_inner.next_page_token.is_empty() {
+                                Some(list_inner.next_page_token)
+                            } else {
+                                None
+                            }),
+                    )
+                }
+                .boxed()
+            },
+            description,
+            retries,
+            span,
+        )
+    }
+
+    fn create_list_collection_ids_request(
+        &self,
+        params: &FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<tonic::Request<ListCollectionIdsRequest>> {
+        self.create_request(ListCollectionIdsRequest {
+            parent: params
+                .parent
+                .as_ref()
+                .unwrap_or_else(|| self.get_documents_path())
+                .clone(),
+            page_size: params.page_size as i32,
+            page_token: params.page_token.clone().unwrap_or_default(),
+            consistency_selector: self
+                .session_params
+                .consistency_selector
+                .as_ref()
+                .map(|selector| selector.try_into())
+                .transpose()?,
+        })
+    }
+
+    // Sends a listing request, retrying it while it fails with retryable database errors
+    fn list_with_retries<'a, R, F>(
+        &'a self,
+        list_fn: F,
+        description: String,
+        retries: usize,
+        span: &'a Span,
+    ) -> BoxFuture<'a, FirestoreResult<R>>
+    where
+        R: Send + 'a,
+        F: Fn() -> BoxFuture<'a, FirestoreResult<R>> + Send + Sync + 'a,
+    {
         async move {
-            let list_request = self.create_list_request(&params)?;
             let begin_utc: DateTime<Utc> = Utc::now();
 
-            match self
-                .client
-                .get()
-                .list_documents(list_request)
-                .map_err(|e| e.into())
-                .await
-            {
-                Ok(listing_response) => {
-                    let list_inner = listing_response.into_inner();
-                    let result = FirestoreListDocResult::new(list_inner.documents).opt_page_token(
-                        if !list_inner.next_page_token.is_empty() {
-                            Some(list_inner.next_page_token)
-                        } else {
-                            None
-                        },
-                    );
+            match list_fn().await {
+                Ok(result) => {
                     let end_query_utc: DateTime<Utc> = Utc::now();
                     let listing_duration = end_query_utc.signed_duration_since(begin_utc);
 
@@ -259,8 +459,8 @@ impl FirestoreDb {
                     );
                     span.in_scope(|| {
                         debug!(
-                            "[DB]: Listing documents in {:?} took {}ms",
-                            params.collection_id,
+                            "[DB]: {} took {}ms",
+                            description,
                             listing_duration.num_milliseconds()
                         );
                     });
@@ -272,12 +472,14 @@ impl FirestoreDb {
                         if db_err.retry_possible && retries < self.options.max_retries =>
                     {
                         warn!(
-                            "[DB]: Listing failed with {}. Retrying: {}/{}",
+                            "[DB]: {} failed with {}. Retrying: {}/{}",
+                            description,
                             db_err,
                             retries + 1,
                             self.options.max_retries
                         );
-                        self.list_doc_with_retries(params, retries + 1, span).await
+                        self.list_with_retries(list_fn, description, retries + 1, span)
+                            .await
                     }
                     _ => Err(err),
                 },
@@ -1,8 +1,14 @@
 use crate::db::safe_document_path;
 use crate::errors::*;
-use crate::timestamp_utils::to_timestamp;
-use crate::{FirestoreDb, FirestoreQueryParams, FirestoreResult};
+use crate::timestamp_utils::{from_timestamp, to_timestamp};
+use crate::{
+    FirestoreDb, FirestoreListenDocumentChange, FirestoreListenDocumentRemove,
+    FirestoreListenEvent, FirestoreListenExistenceFilter, FirestoreListenTargetChange,
+    FirestoreQueryParams, FirestoreResult,
+};
 use async_trait::async_trait;
+use backoff::backoff::Backoff;
+use backoff::{ExponentialBackoff, ExponentialBackoffBuilder};
 use chrono::prelude::*;
 use futures::stream::BoxStream;
 use futures::StreamExt;
@@ -11,11 +17,12 @@ use futures::TryStreamExt;
 use gcloud_sdk::google::firestore::v1::*;
 use rsb_derive::*;
 use rvstruct::ValueStruct;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 use tracing::*;
 
@@ -47,12 +54,26 @@ pub enum FirestoreListenerTargetResumeType {
     ReadTime(DateTime<Utc>),
 }
 
+/// Changes of targets requested on a live listen stream.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum FirestoreListenerTargetUpdate {
+    Add(FirestoreListenerTargetParams),
+    Remove(FirestoreListenerTarget),
+}
+
 #[async_trait]
 pub trait FirestoreListenSupport {
     async fn listen_doc_changes<'a, 'b>(
         &'a self,
         targets: Vec<FirestoreListenerTargetParams>,
     ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>>;
+
+    async fn listen_doc_changes_with_updates<'a, 'b>(
+        &'a self,
+        targets: Vec<FirestoreListenerTargetParams>,
+        target_updates: UnboundedReceiver<FirestoreListenerTargetUpdate>,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>>;
 }
 
 #[async_trait]
@@ -60,15 +81,25 @@ impl FirestoreListenSupport for FirestoreDb {
     async fn listen_doc_changes<'a, 'b>(
         &'a self,
         targets: Vec<FirestoreListenerTargetParams>,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
+        self.listen_doc_changes_with_updates(targets, tokio::sync::mpsc::unbounded_channel().1)
+            .await
+    }
+
+    async fn listen_doc_changes_with_updates<'a, 'b>(
+        &'a self,
+        targets: Vec<FirestoreListenerTargetParams>,
+        target_updates: UnboundedReceiver<FirestoreListenerTargetUpdate>,
     ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
         let listen_requests = targets
             .into_iter()
             .map(|target_params| self.create_listen_request(target_params))
             .collect::<FirestoreResult<Vec<ListenRequest>>>()?;
 
-        let request = tonic::Request::new(
-            futures::stream::iter(listen_requests).chain(futures::stream::pending()),
-        );
+        let update_requests = self.create_listen_update_requests(target_updates);
+
+        let request =
+            self.create_request(listen_requests_stream(listen_requests, update_requests))?;
 
         let response = self.client.get().listen(request).await?;
 
@@ -76,6 +107,20 @@ impl FirestoreListenSupport for FirestoreDb {
     }
 }
 
+// The server closes the listen stream when the requests stream ends,
+// so it is kept open even when there are no more target updates to send
+fn listen_requests_stream<U>(
+    listen_requests: Vec<ListenRequest>,
+    update_requests: U,
+) -> impl futures::Stream<Item = ListenRequest> + Send + 'static
+where
+    U: futures::Stream<Item = ListenRequest> + Send + 'static,
+{
+    futures::stream::iter(listen_requests)
+        .chain(update_requests)
+        .chain(futures::stream::pending())
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, ValueStruct)]
 pub struct FirestoreListenerTarget(i32);
 
@@ -107,6 +152,42 @@ impl FirestoreDb {
         FirestoreListener::new(self.clone(), storage, params).await
     }
 
+    fn create_listen_update_requests(
+        &self,
+        target_updates: UnboundedReceiver<FirestoreListenerTargetUpdate>,
+    ) -> impl futures::Stream<Item = ListenRequest> + Send + 'static {
+        let db = self.clone();
+        tokio_stream::StreamExt::filter_map(
+            tokio_stream::wrappers::UnboundedReceiverStream::new(target_updates),
+            move |target_update| {
+                let tried = match target_update {
+                    FirestoreListenerTargetUpdate::Add(target_params) => {
+                        db.create_listen_request(target_params)
+                    }
+                    FirestoreListenerTargetUpdate::Remove(target) => {
+                        Ok(db.create_remove_listen_request(target))
+                    }
+                };
+                tried
+                    .map_err(|err| {
+                        error!("Listen target update error occurred {:?}.", err);
+                        err
+                    })
+                    .ok()
+            },
+        )
+    }
+
+    fn create_remove_listen_request(&self, target: FirestoreListenerTarget) -> ListenRequest {
+        ListenRequest {
+            database: self.get_database_path().to_string(),
+            labels: HashMap::new(),
+            target_change: Some(listen_request::TargetChange::RemoveTarget(
+                target.into_value(),
+            )),
+        }
+    }
+
     fn create_listen_request(
         &self,
         target_params: FirestoreListenerTargetParams,
@@ -119,6 +200,7 @@ impl FirestoreDb {
                 once: target_params.add_target_once.unwrap_or(false),
                 target_type: Some(match target_params.target_type {
                     FirestoreTargetType::Query(query_params) => {
+                        query_params.validate()?;
                         target::TargetType::Query(target::QueryTarget {
                             parent: query_params
                                 .parent
@@ -176,13 +258,82 @@ pub trait FirestoreResumeStateStorage {
         target: &FirestoreListenerTarget,
         token: FirestoreListenerToken,
     ) -> BoxedErrResult<()>;
-}
 
-pub type FirestoreListenEvent = listen_response::ResponseType;
+    /// Writes the resume tokens which have been buffered by the storage.
+    /// Called when the listener shuts down.
+    async fn flush(&self) -> BoxedErrResult<()> {
+        Ok(())
+    }
+
+    /// Removes the resume state of a target which has been removed from the listener.
+    async fn remove_resume_state(&self, _target: &FirestoreListenerTarget) -> BoxedErrResult<()> {
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone, Builder)]
 pub struct FirestoreListenerParams {
+    /// Fixed delay between reconnects, used when `retry_backoff` isn't specified.
     pub retry_delay: Option<std::time::Duration>,
+    pub retry_backoff: Option<FirestoreListenerRetryBackoff>,
+}
+
+/// Exponential backoff for reconnects of a listener.
+/// `max_attempts` limits the number of consecutive failed attempts,
+/// after which the listener stops with the `Failed` status.
+#[derive(Debug, Clone, Builder)]
+pub struct FirestoreListenerRetryBackoff {
+    #[default = "std::time::Duration::from_millis(500)"]
+    pub initial_interval: std::time::Duration,
+    #[default = "std::time::Duration::from_secs(60)"]
+    pub max_interval: std::time::Duration,
+    #[default = "2.0"]
+    pub multiplier: f64,
+    #[default = "0.5"]
+    pub jitter: f64,
+    pub max_attempts: Option<u32>,
+}
+
+impl FirestoreListenerRetryBackoff {
+    pub(crate) fn create_backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoffBuilder::new()
+            .with_initial_interval(self.initial_interval)
+            .with_max_interval(self.max_interval)
+            .with_multiplier(self.multiplier)
+            .with_randomization_factor(self.jitter)
+            .with_max_elapsed_time(None)
+            .build()
+    }
+
+    pub(crate) fn attempts_exhausted(&self, failed_attempts: u32) -> bool {
+        self.max_attempts
+            .iter()
+            .any(|max_attempts| failed_attempts >= *max_attempts)
+    }
+}
+
+impl FirestoreListenerParams {
+    pub(crate) fn effective_retry_backoff(&self) -> FirestoreListenerRetryBackoff {
+        self.retry_backoff.clone().unwrap_or_else(|| {
+            let retry_delay = self
+                .retry_delay
+                .unwrap_or_else(|| std::time::Duration::from_secs(5));
+            FirestoreListenerRetryBackoff::new()
+                .with_initial_interval(retry_delay)
+                .with_max_interval(retry_delay)
+                .with_multiplier(1.0)
+                .with_jitter(0.0)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FirestoreListenerStatus {
+    NotStarted,
+    Listening,
+    Reconnecting(u32),
+    Stopped,
+    Failed(String),
 }
 
 pub struct FirestoreListener<D, S>
@@ -197,6 +348,9 @@ where
     shutdown_flag: Arc<AtomicBool>,
     shutdown_handle: Option<JoinHandle<()>>,
     shutdown_writer: Option<Arc<UnboundedSender<i8>>>,
+    target_updates_writer: Option<UnboundedSender<FirestoreListenerTargetUpdate>>,
+    status: Arc<RwLock<FirestoreListenerStatus>>,
+    last_error: Arc<RwLock<Option<String>>>,
 }
 
 impl<D, S> FirestoreListener<D, S>
@@ -217,19 +371,164 @@ where
             shutdown_flag: Arc::new(AtomicBool::new(false)),
             shutdown_handle: None,
             shutdown_writer: None,
+            target_updates_writer: None,
+            status: Arc::new(RwLock::new(FirestoreListenerStatus::NotStarted)),
+            last_error: Arc::new(RwLock::new(None)),
         })
     }
 
+    /// The current status of the listener, including terminal failures
+    /// after the reconnect attempts have been exhausted.
+    pub async fn status(&self) -> FirestoreListenerStatus {
+        self.status.read().await.clone()
+    }
+
+    /// The last error occurred while listening, including the errors the listener recovered from.
+    pub async fn last_error(&self) -> Option<String> {
+        self.last_error.read().await.clone()
+    }
+
+    /// Adds a target to listen. When the listener is already running,
+    /// the target is added to the live listen stream.
     pub fn add_target(&mut self, target: FirestoreListenerTargetParams) -> FirestoreResult<()> {
-        self.targets.push(target);
+        if self
+            .targets
+            .iter()
+            .any(|existing| existing.target == target.target)
+        {
+            return Err(FirestoreError::InvalidParametersError(
+                FirestoreInvalidParametersError::new(FirestoreInvalidParametersPublicDetails::new(
+                    "target".into(),
+                    format!("Target {} has already been added", target.target.value()),
+                )),
+            ));
+        }
+
+        if let FirestoreTargetType::Query(ref query_params) = target.target_type {
+            query_params.validate()?;
+        }
+
+        self.targets.push(target.clone());
+
+        if let Some(ref target_updates_writer) = self.target_updates_writer {
+            target_updates_writer
+                .send(FirestoreListenerTargetUpdate::Add(target))
+                .ok();
+        }
         Ok(())
     }
 
+    /// Removes a target together with its resume state in the storage.
+    /// When the listener is already running, the target is removed from the live listen stream.
+    pub async fn remove_target(&mut self, target: &FirestoreListenerTarget) -> FirestoreResult<()> {
+        if !self
+            .targets
+            .iter()
+            .any(|existing| &existing.target == target)
+        {
+            return Err(FirestoreError::InvalidParametersError(
+                FirestoreInvalidParametersError::new(FirestoreInvalidParametersPublicDetails::new(
+                    "target".into(),
+                    format!("Target {} hasn't been added", target.value()),
+                )),
+            ));
+        }
+
+        self.targets.retain(|existing| &existing.target != target);
+
+        // A running listener removes the resume state itself,
+        // so it isn't updated again by the responses received before the removal
+        if let Some(ref target_updates_writer) = self.target_updates_writer {
+            if target_updates_writer
+                .send(FirestoreListenerTargetUpdate::Remove(target.clone()))
+                .is_ok()
+            {
+                return Ok(());
+            }
+        }
+
+        remove_resume_state(&self.storage, target).await
+    }
+
     pub async fn start<FN, F>(&mut self, cb: FN) -> FirestoreResult<()>
     where
         FN: Fn(FirestoreListenEvent) -> F + Send + Sync + 'static,
         F: Future<Output = BoxedErrResult<()>> + Send + Sync + 'static,
     {
+        let mut events_stream = self.start_stream().await?;
+
+        self.shutdown_handle = Some(tokio::spawn(async move {
+            while let Some(tried) = events_stream.next().await {
+                match tried {
+                    Ok(listen_event) => {
+                        if let Err(err) = cb(listen_event).await {
+                            error!("Listener callback function error occurred {:?}.", err);
+                        }
+                    }
+                    Err(err) => {
+                        error!("Listener event handling error occurred {:?}.", err);
+                    }
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    /// Starts listening and returns the events as a stream instead of delivering them to a callback.
+    /// Events are read from Firestore only as fast as the stream is consumed.
+    /// The stream finishes on `shutdown` or when it is dropped.
+    pub async fn start_stream(
+        &mut self,
+    ) -> FirestoreResult<BoxStream<'static, FirestoreResult<FirestoreListenEvent>>> {
+        let stream_state = self.create_stream_state().await?;
+        let last_error = self.last_error.clone();
+
+        Ok(futures::stream::unfold(
+            (stream_state, last_error),
+            |(mut stream_state, last_error)| async move {
+                let tried = stream_state.next_event().await?;
+                if let Err(ref err) = tried {
+                    *last_error.write().await = Some(err.to_string());
+                }
+                Some((tried, (stream_state, last_error)))
+            },
+        )
+        .boxed())
+    }
+
+    /// Starts listening and returns the raw responses for the listeners on top of this one,
+    /// which need all of the responses including the consistent points of the stream.
+    /// Reconnects, backoff and resume tokens are handled the same way as for `start_stream`.
+    pub(crate) async fn start_responses_stream(
+        &mut self,
+    ) -> FirestoreResult<BoxStream<'static, FirestoreResult<listen_response::ResponseType>>> {
+        let stream_state = self.create_stream_state().await?;
+        let last_error = self.last_error.clone();
+
+        Ok(futures::stream::unfold(
+            (stream_state, last_error),
+            |(mut stream_state, last_error)| async move {
+                let tried = stream_state.next_response().await?;
+                if let Err(ref err) = tried {
+                    *last_error.write().await = Some(err.to_string());
+                }
+                Some((tried, (stream_state, last_error)))
+            },
+        )
+        .boxed())
+    }
+
+    pub(crate) fn target_updates_writer(
+        &self,
+    ) -> Option<UnboundedSender<FirestoreListenerTargetUpdate>> {
+        self.target_updates_writer.clone()
+    }
+
+    pub(crate) fn last_error_handle(&self) -> Arc<RwLock<Option<String>>> {
+        self.last_error.clone()
+    }
+
+    async fn create_stream_state(&mut self) -> FirestoreResult<FirestoreListenerStreamState<D, S>> {
         info!(
             "Starting a Firestore listener for targets: {:?}...",
             &self.targets.len()
@@ -259,16 +558,29 @@ where
             tokio::sync::mpsc::unbounded_channel();
 
         self.shutdown_writer = Some(Arc::new(tx));
-        self.shutdown_handle = Some(tokio::spawn(Self::listener_loop(
-            self.db.clone(),
-            self.storage.clone(),
-            self.shutdown_flag.clone(),
-            initial_states,
-            self.listener_params.clone(),
-            rx,
-            cb,
-        )));
-        Ok(())
+
+        let (target_updates_writer, target_updates_receiver) =
+            tokio::sync::mpsc::unbounded_channel();
+        self.target_updates_writer = Some(target_updates_writer);
+
+        let retry_backoff = self.listener_params.effective_retry_backoff();
+
+        Ok(FirestoreListenerStreamState {
+            db: self.db.clone(),
+            storage: self.storage.clone(),
+            shutdown_flag: self.shutdown_flag.clone(),
+            targets_state: initial_states,
+            shutdown_receiver: rx,
+            target_updates_receiver,
+            events_tracker: FirestoreListenEventsTracker::new(),
+            listen_stream: None,
+            listen_updates_writer: None,
+            backoff: retry_backoff.create_backoff(),
+            retry_backoff,
+            failed_attempts: 0,
+            failed: false,
+            status: self.status.clone(),
+        })
     }
 
     pub async fn shutdown(&mut self) -> FirestoreResult<()> {
@@ -282,86 +594,630 @@ where
                 warn!("Firestore listener exit error: {}...", err);
             };
         }
+        {
+            let mut status = self.status.write().await;
+            if !matches!(*status, FirestoreListenerStatus::Failed(_)) {
+                *status = FirestoreListenerStatus::Stopped;
+            }
+        }
+        self.storage.flush().await.map_err(|err| {
+            FirestoreError::SystemError(FirestoreSystemError::new(
+                FirestoreErrorPublicGenericDetails::new("SystemError".into()),
+                format!("Listener token storage error: {}", err),
+            ))
+        })?;
         debug!("Shutting down Firestore listener has been finished...");
         Ok(())
     }
+}
 
-    async fn listener_loop<FN, F>(
-        db: D,
-        storage: S,
-        shutdown_flag: Arc<AtomicBool>,
-        mut targets_state: HashMap<FirestoreListenerTarget, FirestoreListenerTargetParams>,
-        listener_params: FirestoreListenerParams,
-        mut shutdown_receiver: UnboundedReceiver<i8>,
-        cb: FN,
-    ) where
-        D: FirestoreListenSupport + Clone + Send + Sync,
-        FN: Fn(FirestoreListenEvent) -> F + Send + Sync,
-        F: Future<Output = BoxedErrResult<()>> + Send + Sync,
-    {
-        while !shutdown_flag.load(Ordering::Relaxed) {
-            debug!("Start listening on targets {:?}... ", targets_state.len());
+struct FirestoreListenerStreamState<D, S>
+where
+    D: FirestoreListenSupport,
+    S: FirestoreResumeStateStorage,
+{
+    db: D,
+    storage: S,
+    shutdown_flag: Arc<AtomicBool>,
+    targets_state: HashMap<FirestoreListenerTarget, FirestoreListenerTargetParams>,
+    shutdown_receiver: UnboundedReceiver<i8>,
+    target_updates_receiver: UnboundedReceiver<FirestoreListenerTargetUpdate>,
+    events_tracker: FirestoreListenEventsTracker,
+    listen_stream: Option<BoxStream<'static, FirestoreResult<ListenResponse>>>,
+    listen_updates_writer: Option<UnboundedSender<FirestoreListenerTargetUpdate>>,
+    retry_backoff: FirestoreListenerRetryBackoff,
+    backoff: ExponentialBackoff,
+    failed_attempts: u32,
+    failed: bool,
+    status: Arc<RwLock<FirestoreListenerStatus>>,
+}
 
-            let mut listen_stream = db
-                .listen_doc_changes(targets_state.values().into_iter().cloned().collect())
-                .await
-                .unwrap();
-
-            loop {
-                tokio::select! {
-                        _ = shutdown_receiver.recv() => {
-                            println!("Exiting from listener...");
-                            shutdown_receiver.close();
-                            break;
+#[allow(clippy::large_enum_variant)]
+enum FirestoreListenerStreamInput {
+    TargetUpdate(FirestoreListenerTargetUpdate),
+    Response(FirestoreResult<Option<ListenResponse>>),
+}
+
+impl<D, S> FirestoreListenerStreamState<D, S>
+where
+    D: FirestoreListenSupport + Clone + Send + Sync + 'static,
+    S: FirestoreResumeStateStorage + Clone + Send + Sync + 'static,
+{
+    async fn next_event(&mut self) -> Option<FirestoreResult<FirestoreListenEvent>> {
+        loop {
+            match self.next_response().await? {
+                Ok(response_type) => match self.events_tracker.handle_response(response_type) {
+                    Ok(Some(listen_event)) => return Some(Ok(listen_event)),
+                    Ok(None) => {}
+                    Err(err) => return Some(Err(err)),
+                },
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+
+    async fn next_response(&mut self) -> Option<FirestoreResult<listen_response::ResponseType>> {
+        while !self.shutdown_flag.load(Ordering::Relaxed) && !self.failed {
+            let listen_stream = match self.listen_stream {
+                Some(ref mut listen_stream) => listen_stream,
+                None => {
+                    debug!(
+                        "Start listening on targets {:?}... ",
+                        self.targets_state.len()
+                    );
+                    let (listen_updates_writer, listen_updates_receiver) =
+                        tokio::sync::mpsc::unbounded_channel();
+                    match self
+                        .db
+                        .listen_doc_changes_with_updates(
+                            self.targets_state.values().cloned().collect(),
+                            listen_updates_receiver,
+                        )
+                        .await
+                    {
+                        Ok(listen_stream) => {
+                            *self.status.write().await = FirestoreListenerStatus::Listening;
+                            self.listen_updates_writer = Some(listen_updates_writer);
+                            self.listen_stream.insert(listen_stream)
+                        }
+                        Err(err) => {
+                            if let Err(err) = self.retry_after_failure(err).await {
+                                return Some(Err(err));
+                            }
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            let input = tokio::select! {
+                _ = self.shutdown_receiver.recv() => {
+                    debug!("Exiting from listener...");
+                    self.shutdown_receiver.close();
+                    return None;
+                }
+                Some(target_update) = self.target_updates_receiver.recv() => {
+                    FirestoreListenerStreamInput::TargetUpdate(target_update)
+                }
+                tried = listen_stream.try_next() => FirestoreListenerStreamInput::Response(tried)
+            };
+
+            if self.shutdown_flag.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let tried = match input {
+                FirestoreListenerStreamInput::TargetUpdate(target_update) => {
+                    if let Err(err) = self.apply_target_update(target_update).await {
+                        return Some(Err(err));
+                    }
+                    continue;
+                }
+                FirestoreListenerStreamInput::Response(tried) => tried,
+            };
+
+            match tried {
+                Ok(Some(ListenResponse {
+                    response_type: Some(response_type),
+                })) => {
+                    trace!(
+                        "Received a listen response event to handle: {:?}",
+                        response_type
+                    );
+                    self.failed_attempts = 0;
+                    self.backoff.reset();
+                    if let listen_response::ResponseType::TargetChange(ref target_change) =
+                        response_type
+                    {
+                        if let Err(err) = self.update_resume_tokens(target_change).await {
+                            return Some(Err(err));
                         }
-                        tried = listen_stream.try_next() => {
-                            if shutdown_flag.load(Ordering::Relaxed) {
-                                break;
+                    }
+
+                    return Some(Ok(response_type));
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    self.disconnect();
+                    debug!("Listen stream has been closed by the server");
+                    self.delay_reconnect().await;
+                }
+                Err(err) => {
+                    self.disconnect();
+                    if let Err(err) = self.retry_after_failure(err).await {
+                        return Some(Err(err));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn disconnect(&mut self) {
+        self.listen_stream = None;
+        self.listen_updates_writer = None;
+    }
+
+    async fn apply_target_update(
+        &mut self,
+        target_update: FirestoreListenerTargetUpdate,
+    ) -> FirestoreResult<()> {
+        match target_update {
+            FirestoreListenerTargetUpdate::Add(target_params) => {
+                debug!("Adding listener target {:?}...", target_params.target);
+                let resume_state = self
+                    .storage
+                    .read_resume_state(&target_params.target)
+                    .await
+                    .map_err(|err| {
+                        FirestoreError::SystemError(FirestoreSystemError::new(
+                            FirestoreErrorPublicGenericDetails::new("SystemError".into()),
+                            format!("Listener target init error: {}", err),
+                        ))
+                    })?;
+                let target_params = target_params.opt_resume_type(resume_state);
+
+                self.targets_state
+                    .insert(target_params.target.clone(), target_params.clone());
+                self.send_listen_update(FirestoreListenerTargetUpdate::Add(target_params));
+            }
+            FirestoreListenerTargetUpdate::Remove(target) => {
+                debug!("Removing listener target {:?}...", target);
+                self.targets_state.remove(&target);
+                self.events_tracker
+                    .forget_targets(std::slice::from_ref(&target));
+                remove_resume_state(&self.storage, &target).await?;
+                self.send_listen_update(FirestoreListenerTargetUpdate::Remove(target));
+            }
+        }
+        Ok(())
+    }
+
+    // Target updates reach the server only with a live stream,
+    // otherwise they're applied on reconnect from the targets state
+    fn send_listen_update(&mut self, target_update: FirestoreListenerTargetUpdate) {
+        if let Some(ref listen_updates_writer) = self.listen_updates_writer {
+            listen_updates_writer.send(target_update).ok();
+        }
+    }
+
+    async fn update_resume_tokens(&mut self, target_change: &TargetChange) -> FirestoreResult<()> {
+        if !target_change.resume_token.is_empty() {
+            for target_id_num in &target_change.target_ids {
+                if let Some(target) = self
+                    .targets_state
+                    .get_mut(&FirestoreListenerTarget::new(*target_id_num))
+                {
+                    let new_token: FirestoreListenerToken =
+                        target_change.resume_token.clone().into();
+
+                    self.storage
+                        .update_resume_token(&target.target, new_token.clone())
+                        .await
+                        .map_err(|err| {
+                            FirestoreError::SystemError(FirestoreSystemError::new(
+                                FirestoreErrorPublicGenericDetails::new("SystemError".into()),
+                                format!("Listener token storage error: {}", err),
+                            ))
+                        })?;
+
+                    target.resume_type = Some(FirestoreListenerTargetResumeType::Token(new_token))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Waits before reconnecting, or fails the listener when the attempts are exhausted
+    // or the error can't be fixed by reconnecting
+    async fn retry_after_failure(&mut self, err: FirestoreError) -> FirestoreResult<()> {
+        self.failed_attempts += 1;
+
+        let attempts_exhausted = self.retry_backoff.attempts_exhausted(self.failed_attempts);
+
+        if attempts_exhausted || matches!(err, FirestoreError::InvalidParametersError(_)) {
+            error!(
+                "Listener failed after {} attempts: {:?}",
+                self.failed_attempts, err
+            );
+            self.failed = true;
+            self.disconnect();
+            *self.status.write().await = FirestoreListenerStatus::Failed(err.to_string());
+            return Err(err);
+        }
+
+        debug!("Listen error occurred {:?}", err);
+        self.delay_reconnect().await;
+        Ok(())
+    }
+
+    // The backoff is reset only on received responses, so the streams closed by the server
+    // without any responses are reconnected with growing delays too
+    async fn delay_reconnect(&mut self) {
+        let effective_delay = self
+            .backoff
+            .next_backoff()
+            .unwrap_or(self.retry_backoff.max_interval);
+        *self.status.write().await = FirestoreListenerStatus::Reconnecting(self.failed_attempts);
+        debug!("Restarting listening in {:?}...", effective_delay);
+        tokio::time::sleep(effective_delay).await;
+    }
+}
+
+async fn remove_resume_state<S>(
+    storage: &S,
+    target: &FirestoreListenerTarget,
+) -> FirestoreResult<()>
+where
+    S: FirestoreResumeStateStorage + Sync,
+{
+    storage.remove_resume_state(target).await.map_err(|err| {
+        FirestoreError::SystemError(FirestoreSystemError::new(
+            FirestoreErrorPublicGenericDetails::new("SystemError".into()),
+            format!("Listener token storage error: {}", err),
+        ))
+    })
+}
+
+/// Converts raw listen responses to typed events, remembering which documents
+/// have already been seen for each target to distinguish additions from modifications.
+struct FirestoreListenEventsTracker {
+    target_docs: HashMap<FirestoreListenerTarget, HashSet<String>>,
+}
+
+impl FirestoreListenEventsTracker {
+    fn new() -> Self {
+        Self {
+            target_docs: HashMap::new(),
+        }
+    }
+
+    fn handle_response(
+        &mut self,
+        response_type: listen_response::ResponseType,
+    ) -> FirestoreResult<Option<FirestoreListenEvent>> {
+        match response_type {
+            listen_response::ResponseType::DocumentChange(doc_change) => {
+                let target_ids = Self::to_targets(doc_change.target_ids);
+                let removed_target_ids = Self::to_targets(doc_change.removed_target_ids);
+
+                match doc_change.document {
+                    Some(document) => {
+                        self.forget_document(&document.name, &removed_target_ids);
+
+                        if target_ids.is_empty() {
+                            Ok(Some(FirestoreListenEvent::Removed(
+                                FirestoreListenDocumentRemove::new(
+                                    document.name,
+                                    removed_target_ids,
+                                ),
+                            )))
+                        } else {
+                            let known = target_ids.iter().any(|target| {
+                                self.target_docs
+                                    .get(target)
+                                    .map(|docs| docs.contains(&document.name))
+                                    .unwrap_or(false)
+                            });
+
+                            for target in &target_ids {
+                                self.target_docs
+                                    .entry(target.clone())
+                                    .or_default()
+                                    .insert(document.name.clone());
                             }
-                            else {
-                                match tried {
-                                    Ok(Some(event)) => {
-                                        trace!("Received a listen response event to handle: {:?}", event);
-                                        match event.response_type {
-                                            Some(listen_response::ResponseType::TargetChange(ref target_change))
-                                                if !target_change.resume_token.is_empty() =>
-                                            {
-                                                for target_id_num in &target_change.target_ids {
-                                                    if let Some(mut target) = targets_state.get_mut(&FirestoreListenerTarget::new(*target_id_num)) {
-                                                        let new_token: FirestoreListenerToken = target_change.resume_token.clone().into();
-
-                                                        if let Err(err) = storage.update_resume_token(&target.target, new_token.clone()).await {
-                                                            error!("Listener token storage error occurred {:?}.", err);
-                                                            break;
-                                                        }
-                                                        else {
-                                                            target.resume_type = Some(FirestoreListenerTargetResumeType::Token(new_token))
-                                                        }
-                                                    }
-                                                }
-
-                                            }
-                                            Some(response_type) => {
-                                                if let Err(err) = cb(response_type).await {
-                                                    error!("Listener callback function error occurred {:?}.", err);
-                                                    break;
-                                                }
-                                            }
-                                            None  =>  {}
-                                        }
-                                    }
-                                    Ok(None) => break,
-                                    Err(err) => {
-                                        let effective_delay = listener_params.retry_delay.unwrap_or_else(|| std::time::Duration::from_secs(5));
-                                        debug!("Listen error occurred {:?}. Restarting in {:?}...", err, effective_delay);
-                                        tokio::time::sleep(effective_delay).await;
-                                        break;
-                                    }
-                                }
+
+                            let change = FirestoreListenDocumentChange::from_document(
+                                document,
+                                target_ids,
+                                removed_target_ids,
+                            )?;
+
+                            if known {
+                                Ok(Some(FirestoreListenEvent::Modified(change)))
+                            } else {
+                                Ok(Some(FirestoreListenEvent::Added(change)))
                             }
+                        }
+                    }
+                    None => Ok(None),
+                }
+            }
+            listen_response::ResponseType::DocumentDelete(doc_delete) => {
+                for docs in self.target_docs.values_mut() {
+                    docs.remove(&doc_delete.document);
+                }
+                Ok(Some(FirestoreListenEvent::Deleted(
+                    FirestoreListenDocumentRemove::new(
+                        doc_delete.document,
+                        Self::to_targets(doc_delete.removed_target_ids),
+                    )
+                    .opt_read_time(doc_delete.read_time.map(from_timestamp).transpose()?),
+                )))
+            }
+            listen_response::ResponseType::DocumentRemove(doc_remove) => {
+                let removed_target_ids = Self::to_targets(doc_remove.removed_target_ids);
+                self.forget_document(&doc_remove.document, &removed_target_ids);
+                Ok(Some(FirestoreListenEvent::Removed(
+                    FirestoreListenDocumentRemove::new(doc_remove.document, removed_target_ids)
+                        .opt_read_time(doc_remove.read_time.map(from_timestamp).transpose()?),
+                )))
+            }
+            listen_response::ResponseType::Filter(filter) => Ok(Some(
+                FirestoreListenEvent::ExistenceFilter(FirestoreListenExistenceFilter::new(
+                    FirestoreListenerTarget::new(filter.target_id),
+                    filter.count,
+                )),
+            )),
+            listen_response::ResponseType::TargetChange(target_change) => {
+                let change_type = target_change.target_change_type();
+                let target_ids = Self::to_targets(target_change.target_ids);
+                let event = FirestoreListenTargetChange::new(target_ids.clone())
+                    .opt_read_time(target_change.read_time.map(from_timestamp).transpose()?)
+                    .opt_cause(target_change.cause);
+
+                match change_type {
+                    target_change::TargetChangeType::NoChange
+                    | target_change::TargetChangeType::Add => Ok(None),
+                    target_change::TargetChangeType::Current => {
+                        Ok(Some(FirestoreListenEvent::Current(event)))
+                    }
+                    target_change::TargetChangeType::Reset => {
+                        self.forget_targets(&target_ids);
+                        Ok(Some(FirestoreListenEvent::Reset(event)))
+                    }
+                    target_change::TargetChangeType::Remove => {
+                        self.forget_targets(&target_ids);
+                        Ok(Some(FirestoreListenEvent::TargetRemoved(event)))
                     }
                 }
             }
         }
     }
+
+    fn forget_document(&mut self, document_name: &str, targets: &[FirestoreListenerTarget]) {
+        for target in targets {
+            if let Some(docs) = self.target_docs.get_mut(target) {
+                docs.remove(document_name);
+            }
+        }
+    }
+
+    // An empty list of targets in a target change means all of the targets
+    fn forget_targets(&mut self, targets: &[FirestoreListenerTarget]) {
+        if targets.is_empty() {
+            self.target_docs.clear();
+        } else {
+            for target in targets {
+                self.target_docs.remove(target);
+            }
+        }
+    }
+
+    fn to_targets(target_ids: Vec<i32>) -> Vec<FirestoreListenerTarget> {
+        target_ids
+            .into_iter()
+            .map(FirestoreListenerTarget::new)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fluent_api::tests::mockdb::MockDatabase;
+
+    #[derive(Clone, Default)]
+    struct MockResumeStateStorage {
+        flushed: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl FirestoreResumeStateStorage for MockResumeStateStorage {
+        async fn read_resume_state(
+            &self,
+            _target: &FirestoreListenerTarget,
+        ) -> BoxedErrResult<Option<FirestoreListenerTargetResumeType>> {
+            unreachable!()
+        }
+
+        async fn update_resume_token(
+            &self,
+            _target: &FirestoreListenerTarget,
+            _token: FirestoreListenerToken,
+        ) -> BoxedErrResult<()> {
+            unreachable!()
+        }
+
+        async fn flush(&self) -> BoxedErrResult<()> {
+            self.flushed.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    fn target_params(target_id: i32) -> FirestoreListenerTargetParams {
+        FirestoreListenerTargetParams::new(
+            FirestoreListenerTarget::new(target_id),
+            FirestoreTargetType::Query(FirestoreQueryParams::new("test".into())),
+            HashMap::new(),
+        )
+    }
+
+    #[test]
+    fn listener_retry_backoff() {
+        let default_backoff = FirestoreListenerParams::new()
+            .with_retry_delay(std::time::Duration::from_secs(1))
+            .effective_retry_backoff();
+        assert_eq!(
+            default_backoff.create_backoff().next_backoff(),
+            Some(std::time::Duration::from_secs(1))
+        );
+        assert!(!default_backoff.attempts_exhausted(100));
+
+        let limited_backoff = FirestoreListenerRetryBackoff::new().with_max_attempts(3);
+        assert!(!limited_backoff.attempts_exhausted(2));
+        assert!(limited_backoff.attempts_exhausted(3));
+    }
+
+    #[tokio::test]
+    async fn listener_add_remove_targets() {
+        let mut listener = FirestoreListener::new(
+            MockDatabase,
+            MockResumeStateStorage::default(),
+            FirestoreListenerParams::new(),
+        )
+        .await
+        .unwrap();
+
+        assert!(listener.add_target(target_params(1)).is_ok());
+        assert!(listener.add_target(target_params(2)).is_ok());
+        assert!(listener.add_target(target_params(1)).is_err());
+
+        assert!(listener
+            .remove_target(&FirestoreListenerTarget::new(1))
+            .await
+            .is_ok());
+        assert!(listener
+            .remove_target(&FirestoreListenerTarget::new(1))
+            .await
+            .is_err());
+        assert!(listener.add_target(target_params(1)).is_ok());
+        assert_eq!(listener.targets.len(), 2);
+        assert_eq!(listener.status().await, FirestoreListenerStatus::NotStarted);
+
+        listener.shutdown().await.unwrap();
+        assert_eq!(listener.status().await, FirestoreListenerStatus::Stopped);
+        assert!(listener.storage.flushed.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn listen_requests_stream_stays_open() {
+        let mut requests = Box::pin(listen_requests_stream(
+            vec![ListenRequest::default()],
+            futures::stream::empty(),
+        ));
+
+        assert!(requests.next().await.is_some());
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(50), requests.next())
+                .await
+                .is_err()
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct ClosingListenDatabase {
+        connects: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl FirestoreListenSupport for ClosingListenDatabase {
+        async fn listen_doc_changes<'a, 'b>(
+            &'a self,
+            _targets: Vec<FirestoreListenerTargetParams>,
+        ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
+            self.connects.fetch_add(1, Ordering::Relaxed);
+            Ok(futures::stream::empty().boxed())
+        }
+
+        async fn listen_doc_changes_with_updates<'a, 'b>(
+            &'a self,
+            targets: Vec<FirestoreListenerTargetParams>,
+            _target_updates: UnboundedReceiver<FirestoreListenerTargetUpdate>,
+        ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
+            self.listen_doc_changes(targets).await
+        }
+    }
+
+    #[tokio::test]
+    async fn listener_delays_reconnects_of_closed_streams() {
+        let db = ClosingListenDatabase::default();
+        let mut listener = FirestoreListener::new(
+            db.clone(),
+            MockResumeStateStorage::default(),
+            FirestoreListenerParams::new().with_retry_backoff(
+                FirestoreListenerRetryBackoff::new()
+                    .with_initial_interval(std::time::Duration::from_millis(50))
+                    .with_max_interval(std::time::Duration::from_millis(50))
+                    .with_multiplier(1.0)
+                    .with_jitter(0.0),
+            ),
+        )
+        .await
+        .unwrap();
+
+        let mut events = listener.start_stream().await.unwrap();
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(120), events.next())
+                .await
+                .is_err()
+        );
+
+        assert!(db.connects.load(Ordering::Relaxed) <= 3);
+        assert_eq!(
+            listener.status().await,
+            FirestoreListenerStatus::Reconnecting(0)
+        );
+    }
+
+    fn doc_change(name: &str, target_ids: Vec<i32>) -> listen_response::ResponseType {
+        listen_response::ResponseType::DocumentChange(DocumentChange {
+            document: Some(Document {
+                name: name.to_string(),
+                fields: HashMap::new(),
+                create_time: None,
+                update_time: None,
+            }),
+            target_ids,
+            removed_target_ids: vec![],
+        })
+    }
+
+    #[test]
+    fn listen_events_tracker_added_modified() {
+        let mut tracker = FirestoreListenEventsTracker::new();
+
+        assert!(matches!(
+            tracker.handle_response(doc_change("doc1", vec![1])),
+            Ok(Some(ref event @ FirestoreListenEvent::Added(_))) if event.read_time().is_none()
+        ));
+        assert!(matches!(
+            tracker.handle_response(doc_change("doc1", vec![1])),
+            Ok(Some(FirestoreListenEvent::Modified(_)))
+        ));
+        assert!(matches!(
+            tracker.handle_response(listen_response::ResponseType::TargetChange(TargetChange {
+                target_change_type: target_change::TargetChangeType::Reset.into(),
+                target_ids: vec![1],
+                cause: None,
+                resume_token: vec![],
+                read_time: None,
+            })),
+            Ok(Some(FirestoreListenEvent::Reset(_)))
+        ));
+        assert!(matches!(
+            tracker.handle_response(doc_change("doc1", vec![1])),
+            Ok(Some(FirestoreListenEvent::Added(_)))
+        ));
+    }
 }
@@ -0,0 +1,132 @@
+use crate::timestamp_utils::from_timestamp;
+use crate::{FirestoreDb, FirestoreListenerTarget, FirestoreResult};
+use chrono::prelude::*;
+use gcloud_sdk::google::firestore::v1::Document;
+use rsb_derive::*;
+use serde::Deserialize;
+
+#[derive(Debug, Clone)]
+pub enum FirestoreListenEvent {
+    Added(FirestoreListenDocumentChange),
+    Modified(FirestoreListenDocumentChange),
+    Removed(FirestoreListenDocumentRemove),
+    Deleted(FirestoreListenDocumentRemove),
+    Current(FirestoreListenTargetChange),
+    Reset(FirestoreListenTargetChange),
+    TargetRemoved(FirestoreListenTargetChange),
+    ExistenceFilter(FirestoreListenExistenceFilter),
+}
+
+impl FirestoreListenEvent {
+    pub fn document_path(&self) -> Option<&str> {
+        match self {
+            FirestoreListenEvent::Added(change) | FirestoreListenEvent::Modified(change) => {
+                Some(change.document_path())
+            }
+            FirestoreListenEvent::Removed(remove) | FirestoreListenEvent::Deleted(remove) => {
+                Some(remove.document_path.as_str())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn target_ids(&self) -> Vec<FirestoreListenerTarget> {
+        match self {
+            FirestoreListenEvent::Added(change) | FirestoreListenEvent::Modified(change) => {
+                change.target_ids.clone()
+            }
+            FirestoreListenEvent::Removed(remove) | FirestoreListenEvent::Deleted(remove) => {
+                remove.removed_target_ids.clone()
+            }
+            FirestoreListenEvent::Current(target_change)
+            | FirestoreListenEvent::Reset(target_change)
+            | FirestoreListenEvent::TargetRemoved(target_change) => {
+                target_change.target_ids.clone()
+            }
+            FirestoreListenEvent::ExistenceFilter(filter) => vec![filter.target_id.clone()],
+        }
+    }
+
+    /// The read time reported by the server with the event.
+    /// Document changes don't have a read time of their own: it is reported with
+    /// the following target change, and `update_time` of the change is the time of the document version.
+    pub fn read_time(&self) -> Option<DateTime<Utc>> {
+        match self {
+            FirestoreListenEvent::Added(_) | FirestoreListenEvent::Modified(_) => None,
+            FirestoreListenEvent::Removed(remove) | FirestoreListenEvent::Deleted(remove) => {
+                remove.read_time
+            }
+            FirestoreListenEvent::Current(target_change)
+            | FirestoreListenEvent::Reset(target_change)
+            | FirestoreListenEvent::TargetRemoved(target_change) => target_change.read_time,
+            FirestoreListenEvent::ExistenceFilter(_) => None,
+        }
+    }
+
+    pub fn obj<T>(&self) -> FirestoreResult<Option<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        match self {
+            FirestoreListenEvent::Added(change) | FirestoreListenEvent::Modified(change) => {
+                change.obj().map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Builder)]
+pub struct FirestoreListenDocumentChange {
+    pub document: Document,
+    pub target_ids: Vec<FirestoreListenerTarget>,
+    pub removed_target_ids: Vec<FirestoreListenerTarget>,
+    pub update_time: Option<DateTime<Utc>>,
+}
+
+impl FirestoreListenDocumentChange {
+    pub(crate) fn from_document(
+        document: Document,
+        target_ids: Vec<FirestoreListenerTarget>,
+        removed_target_ids: Vec<FirestoreListenerTarget>,
+    ) -> FirestoreResult<Self> {
+        let update_time = document
+            .update_time
+            .clone()
+            .map(from_timestamp)
+            .transpose()?;
+        Ok(Self::new(document, target_ids, removed_target_ids).opt_update_time(update_time))
+    }
+
+    #[inline]
+    pub fn document_path(&self) -> &str {
+        self.document.name.as_str()
+    }
+
+    pub fn obj<T>(&self) -> FirestoreResult<T>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        FirestoreDb::deserialize_doc_to(&self.document)
+    }
+}
+
+#[derive(Debug, Clone, Builder)]
+pub struct FirestoreListenDocumentRemove {
+    pub document_path: String,
+    pub removed_target_ids: Vec<FirestoreListenerTarget>,
+    pub read_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Builder)]
+pub struct FirestoreListenTargetChange {
+    pub target_ids: Vec<FirestoreListenerTarget>,
+    pub read_time: Option<DateTime<Utc>>,
+    pub cause: Option<gcloud_sdk::google::rpc::Status>,
+}
+
+#[derive(Debug, Clone, Builder)]
+pub struct FirestoreListenExistenceFilter {
+    pub target_id: FirestoreListenerTarget,
+    pub count: i32,
+}
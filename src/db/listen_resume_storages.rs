@@ -0,0 +1,551 @@
+use crate::{
+    FirestoreDb, FirestoreListenerTarget, FirestoreListenerTargetResumeType,
+    FirestoreListenerToken, FirestoreResumeStateStorage,
+};
+use async_trait::async_trait;
+use chrono::prelude::*;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rsb_derive::*;
+use rvstruct::ValueStruct;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::RwLock;
+use tracing::*;
+
+type BoxedErrResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Keeps resume tokens in memory, so the listener resumes on reconnects
+/// but not after restarts of the application.
+#[derive(Clone, Debug)]
+pub struct FirestoreMemResumeStateStorage {
+    tokens: Arc<RwLock<HashMap<FirestoreListenerTarget, FirestoreListenerToken>>>,
+}
+
+impl FirestoreMemResumeStateStorage {
+    pub fn new() -> Self {
+        Self {
+            tokens: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+}
+
+impl Default for FirestoreMemResumeStateStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FirestoreResumeStateStorage for FirestoreMemResumeStateStorage {
+    async fn read_resume_state(
+        &self,
+        target: &FirestoreListenerTarget,
+    ) -> BoxedErrResult<Option<FirestoreListenerTargetResumeType>> {
+        Ok(self
+            .tokens
+            .read()
+            .await
+            .get(target)
+            .cloned()
+            .map(FirestoreListenerTargetResumeType::Token))
+    }
+
+    async fn update_resume_token(
+        &self,
+        target: &FirestoreListenerTarget,
+        token: FirestoreListenerToken,
+    ) -> BoxedErrResult<()> {
+        self.tokens.write().await.insert(target.clone(), token);
+        Ok(())
+    }
+
+    async fn remove_resume_state(&self, target: &FirestoreListenerTarget) -> BoxedErrResult<()> {
+        self.tokens.write().await.remove(target);
+        Ok(())
+    }
+}
+
+/// Stores resume tokens in files, one for each target, in the specified directory.
+/// Useful for local development.
+#[derive(Clone, Debug)]
+pub struct FirestoreFilesResumeStateStorage {
+    directory: PathBuf,
+    file_prefix: String,
+}
+
+impl FirestoreFilesResumeStateStorage {
+    pub fn new<P>(directory: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            directory: directory.into(),
+            file_prefix: "firestore-listener".to_string(),
+        }
+    }
+
+    pub fn temp() -> Self {
+        Self::new(std::env::temp_dir())
+    }
+
+    pub fn with_file_prefix<S>(self, file_prefix: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self {
+            file_prefix: file_prefix.as_ref().to_string(),
+            ..self
+        }
+    }
+
+    fn target_file_path(&self, target: &FirestoreListenerTarget) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}.token", self.file_prefix, target.value()))
+    }
+}
+
+#[async_trait]
+impl FirestoreResumeStateStorage for FirestoreFilesResumeStateStorage {
+    async fn read_resume_state(
+        &self,
+        target: &FirestoreListenerTarget,
+    ) -> BoxedErrResult<Option<FirestoreListenerTargetResumeType>> {
+        match tokio::fs::read_to_string(self.target_file_path(target)).await {
+            Ok(str) => Ok(Some(FirestoreListenerTargetResumeType::Token(
+                FirestoreListenerToken::new(hex::decode(str.trim())?),
+            ))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Box::new(err)),
+        }
+    }
+
+    async fn update_resume_token(
+        &self,
+        target: &FirestoreListenerTarget,
+        token: FirestoreListenerToken,
+    ) -> BoxedErrResult<()> {
+        Ok(tokio::fs::write(self.target_file_path(target), hex::encode(token.value())).await?)
+    }
+
+    async fn remove_resume_state(&self, target: &FirestoreListenerTarget) -> BoxedErrResult<()> {
+        match tokio::fs::remove_file(self.target_file_path(target)).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(Box::new(err)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Builder)]
+pub struct FirestoreDbResumeStateStorageParams {
+    pub collection: String,
+    #[default = "std::time::Duration::from_secs(5)"]
+    pub debounce_interval: std::time::Duration,
+}
+
+/// Stores resume tokens as documents in a Firestore collection, using the target ids as document ids.
+/// Token updates are debounced: tokens are written at most once per `debounce_interval`,
+/// and the latest debounced tokens are written when the interval ends or on `flush`.
+#[derive(Clone)]
+pub struct FirestoreDbResumeStateStorage {
+    db: FirestoreDb,
+    params: FirestoreDbResumeStateStorageParams,
+    tokens_writer: FirestoreDebouncedTokensWriter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FirestoreDbResumeStateDoc {
+    token: String,
+    #[serde(with = "crate::serialize_as_timestamp")]
+    updated_at: DateTime<Utc>,
+}
+
+impl FirestoreDbResumeStateStorage {
+    pub fn new(db: FirestoreDb, params: FirestoreDbResumeStateStorageParams) -> Self {
+        let write_db = db.clone();
+        let collection = params.collection.clone();
+        let tokens_writer = FirestoreDebouncedTokensWriter::new(
+            params.debounce_interval,
+            Arc::new(move |target, token| {
+                let db = write_db.clone();
+                let collection = collection.clone();
+                async move {
+                    let state_doc = FirestoreDbResumeStateDoc {
+                        token: hex::encode(token.value()),
+                        updated_at: Utc::now(),
+                    };
+
+                    let _: FirestoreDbResumeStateDoc = db
+                        .fluent()
+                        .update()
+                        .in_col(collection.as_str())
+                        .document_id(target.value().to_string())
+                        .object(&state_doc)
+                        .execute()
+                        .await?;
+                    Ok(())
+                }
+                .boxed()
+            }),
+        );
+
+        Self {
+            db,
+            params,
+            tokens_writer,
+        }
+    }
+}
+
+#[async_trait]
+impl FirestoreResumeStateStorage for FirestoreDbResumeStateStorage {
+    async fn read_resume_state(
+        &self,
+        target: &FirestoreListenerTarget,
+    ) -> BoxedErrResult<Option<FirestoreListenerTargetResumeType>> {
+        if let Some(token) = self.tokens_writer.token(target).await {
+            return Ok(Some(FirestoreListenerTargetResumeType::Token(token)));
+        }
+
+        let state_doc: Option<FirestoreDbResumeStateDoc> = self
+            .db
+            .fluent()
+            .select()
+            .by_id_in(self.params.collection.as_str())
+            .obj()
+            .one(target.value().to_string())
+            .await?;
+
+        state_doc
+            .map(|state_doc| {
+                Ok(FirestoreListenerTargetResumeType::Token(
+                    FirestoreListenerToken::new(hex::decode(state_doc.token)?),
+                ))
+            })
+            .transpose()
+    }
+
+    async fn update_resume_token(
+        &self,
+        target: &FirestoreListenerTarget,
+        token: FirestoreListenerToken,
+    ) -> BoxedErrResult<()> {
+        self.tokens_writer.update(target, token).await
+    }
+
+    /// Writes all tokens which haven't been written yet because of debouncing.
+    async fn flush(&self) -> BoxedErrResult<()> {
+        self.tokens_writer.flush().await
+    }
+
+    async fn remove_resume_state(&self, target: &FirestoreListenerTarget) -> BoxedErrResult<()> {
+        self.tokens_writer.remove(target).await;
+
+        Ok(self
+            .db
+            .fluent()
+            .delete()
+            .from(self.params.collection.as_str())
+            .document_id(target.value().to_string())
+            .execute()
+            .await?)
+    }
+}
+
+type FirestoreResumeTokenWriteFn = Arc<
+    dyn Fn(
+            FirestoreListenerTarget,
+            FirestoreListenerToken,
+        ) -> BoxFuture<'static, BoxedErrResult<()>>
+        + Send
+        + Sync,
+>;
+
+// Debounces token updates independently of where the tokens are written
+#[derive(Clone)]
+struct FirestoreDebouncedTokensWriter {
+    debounce_interval: std::time::Duration,
+    write_fn: FirestoreResumeTokenWriteFn,
+    state: Arc<RwLock<FirestoreDebouncedTokensState>>,
+}
+
+struct FirestoreDebouncedTokensState {
+    tokens: HashMap<FirestoreListenerTarget, FirestoreListenerToken>,
+    pending_targets: HashSet<FirestoreListenerTarget>,
+    last_written: Option<Instant>,
+    trailing_write_scheduled: bool,
+}
+
+impl FirestoreDebouncedTokensWriter {
+    fn new(debounce_interval: std::time::Duration, write_fn: FirestoreResumeTokenWriteFn) -> Self {
+        Self {
+            debounce_interval,
+            write_fn,
+            state: Arc::new(RwLock::new(FirestoreDebouncedTokensState {
+                tokens: HashMap::new(),
+                pending_targets: HashSet::new(),
+                last_written: None,
+                trailing_write_scheduled: false,
+            })),
+        }
+    }
+
+    async fn token(&self, target: &FirestoreListenerTarget) -> Option<FirestoreListenerToken> {
+        self.state.read().await.tokens.get(target).cloned()
+    }
+
+    async fn update(
+        &self,
+        target: &FirestoreListenerTarget,
+        token: FirestoreListenerToken,
+    ) -> BoxedErrResult<()> {
+        let pending_tokens = {
+            let mut state = self.state.write().await;
+            state.tokens.insert(target.clone(), token);
+            state.pending_targets.insert(target.clone());
+
+            let debounced = state
+                .last_written
+                .iter()
+                .any(|last_written| last_written.elapsed() < self.debounce_interval);
+
+            if debounced {
+                trace!("Debouncing the resume token update for target {:?}", target);
+                self.schedule_trailing_write(&mut state);
+                vec![]
+            } else {
+                state.last_written = Some(Instant::now());
+                Self::take_pending_tokens(&mut state)
+            }
+        };
+
+        self.write_tokens(pending_tokens).await
+    }
+
+    async fn flush(&self) -> BoxedErrResult<()> {
+        let pending_tokens = {
+            let mut state = self.state.write().await;
+            state.last_written = Some(Instant::now());
+            state.trailing_write_scheduled = false;
+            Self::take_pending_tokens(&mut state)
+        };
+        self.write_tokens(pending_tokens).await
+    }
+
+    async fn remove(&self, target: &FirestoreListenerTarget) {
+        let mut state = self.state.write().await;
+        state.tokens.remove(target);
+        state.pending_targets.remove(target);
+    }
+
+    // Writes the debounced tokens when the debounce interval ends,
+    // so the latest tokens are stored even without further updates
+    fn schedule_trailing_write(&self, state: &mut FirestoreDebouncedTokensState) {
+        if state.trailing_write_scheduled {
+            return;
+        }
+        state.trailing_write_scheduled = true;
+
+        let delay = state
+            .last_written
+            .map(|last_written| {
+                self.debounce_interval
+                    .saturating_sub(last_written.elapsed())
+            })
+            .unwrap_or_default();
+        let tokens_writer = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if let Err(err) = tokens_writer.flush().await {
+                warn!("Unable to write debounced resume tokens: {}", err);
+            }
+        });
+    }
+
+    fn take_pending_tokens(
+        state: &mut FirestoreDebouncedTokensState,
+    ) -> Vec<(FirestoreListenerTarget, FirestoreListenerToken)> {
+        let pending_targets = std::mem::take(&mut state.pending_targets);
+        pending_targets
+            .into_iter()
+            .filter_map(|target| {
+                state
+                    .tokens
+                    .get(&target)
+                    .cloned()
+                    .map(|token| (target, token))
+            })
+            .collect()
+    }
+
+    async fn write_tokens(
+        &self,
+        tokens: Vec<(FirestoreListenerTarget, FirestoreListenerToken)>,
+    ) -> BoxedErrResult<()> {
+        let mut tokens = tokens.into_iter();
+        while let Some((target, token)) = tokens.next() {
+            if let Err(err) = (self.write_fn)(target.clone(), token).await {
+                // Keeping the tokens to write them next time
+                let mut state = self.state.write().await;
+                state.pending_targets.insert(target);
+                state
+                    .pending_targets
+                    .extend(tokens.map(|(target, _)| target));
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mem_resume_state_storage() {
+        let storage = FirestoreMemResumeStateStorage::default();
+        let target = FirestoreListenerTarget::new(42);
+
+        assert!(storage.read_resume_state(&target).await.unwrap().is_none());
+
+        storage
+            .update_resume_token(&target, FirestoreListenerToken::new(vec![1, 2, 3]))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            storage.read_resume_state(&target).await.unwrap(),
+            Some(FirestoreListenerTargetResumeType::Token(token)) if token.value() == &vec![1, 2, 3]
+        ));
+
+        storage.remove_resume_state(&target).await.unwrap();
+        assert!(storage.read_resume_state(&target).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn files_resume_state_storage() {
+        let storage = FirestoreFilesResumeStateStorage::temp()
+            .with_file_prefix(format!("firestore-test-{}", std::process::id()));
+        let target = FirestoreListenerTarget::new(17);
+
+        assert!(storage.read_resume_state(&target).await.unwrap().is_none());
+
+        storage
+            .update_resume_token(&target, FirestoreListenerToken::new(vec![4, 5, 6]))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            storage.read_resume_state(&target).await.unwrap(),
+            Some(FirestoreListenerTargetResumeType::Token(token)) if token.value() == &vec![4, 5, 6]
+        ));
+
+        storage.remove_resume_state(&target).await.unwrap();
+        assert!(storage.read_resume_state(&target).await.unwrap().is_none());
+    }
+
+    fn recording_tokens_writer(
+        debounce_interval: std::time::Duration,
+    ) -> (
+        FirestoreDebouncedTokensWriter,
+        Arc<std::sync::Mutex<Vec<(FirestoreListenerTarget, FirestoreListenerToken)>>>,
+    ) {
+        let written = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let write_fn_written = written.clone();
+        let tokens_writer = FirestoreDebouncedTokensWriter::new(
+            debounce_interval,
+            Arc::new(move |target, token| {
+                write_fn_written.lock().unwrap().push((target, token));
+                futures::future::ready(Ok(())).boxed()
+            }),
+        );
+        (tokens_writer, written)
+    }
+
+    fn written_tokens(
+        written: &std::sync::Mutex<Vec<(FirestoreListenerTarget, FirestoreListenerToken)>>,
+    ) -> Vec<(i32, Vec<u8>)> {
+        written
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(target, token)| (*target.value(), token.value().clone()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn debounced_tokens_writer_coalesces_updates() {
+        let (tokens_writer, written) = recording_tokens_writer(std::time::Duration::from_secs(60));
+        let target = FirestoreListenerTarget::new(1);
+
+        for token in 1..=3 {
+            tokens_writer
+                .update(&target, FirestoreListenerToken::new(vec![token]))
+                .await
+                .unwrap();
+        }
+
+        // Only the first update is written within the debounce interval
+        assert_eq!(written_tokens(&written), vec![(1, vec![1])]);
+        assert_eq!(
+            tokens_writer.token(&target).await.unwrap().value(),
+            &vec![3]
+        );
+    }
+
+    #[tokio::test]
+    async fn debounced_tokens_writer_writes_on_trailing_edge() {
+        let (tokens_writer, written) =
+            recording_tokens_writer(std::time::Duration::from_millis(50));
+        let target = FirestoreListenerTarget::new(1);
+
+        tokens_writer
+            .update(&target, FirestoreListenerToken::new(vec![1]))
+            .await
+            .unwrap();
+        tokens_writer
+            .update(&target, FirestoreListenerToken::new(vec![2]))
+            .await
+            .unwrap();
+        tokens_writer
+            .update(&target, FirestoreListenerToken::new(vec![3]))
+            .await
+            .unwrap();
+        assert_eq!(written_tokens(&written), vec![(1, vec![1])]);
+
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        assert_eq!(written_tokens(&written), vec![(1, vec![1]), (1, vec![3])]);
+    }
+
+    #[tokio::test]
+    async fn debounced_tokens_writer_flushes_pending_tokens() {
+        let (tokens_writer, written) = recording_tokens_writer(std::time::Duration::from_secs(60));
+        let target = FirestoreListenerTarget::new(1);
+        let other_target = FirestoreListenerTarget::new(2);
+
+        tokens_writer
+            .update(&target, FirestoreListenerToken::new(vec![1]))
+            .await
+            .unwrap();
+        tokens_writer
+            .update(&target, FirestoreListenerToken::new(vec![2]))
+            .await
+            .unwrap();
+        tokens_writer
+            .update(&other_target, FirestoreListenerToken::new(vec![3]))
+            .await
+            .unwrap();
+        tokens_writer.remove(&other_target).await;
+
+        tokens_writer.flush().await.unwrap();
+        assert_eq!(written_tokens(&written), vec![(1, vec![1]), (1, vec![2])]);
+
+        // Nothing is pending after the flush
+        tokens_writer.flush().await.unwrap();
+        assert_eq!(written_tokens(&written).len(), 2);
+    }
+}
@@ -0,0 +1,866 @@
+use crate::errors::*;
+use crate::timestamp_utils::from_timestamp;
+use crate::{
+    FirestoreDb, FirestoreListenSupport, FirestoreListener, FirestoreListenerParams,
+    FirestoreListenerRetryBackoff, FirestoreListenerStatus, FirestoreListenerTarget,
+    FirestoreListenerTargetParams, FirestoreListenerTargetUpdate, FirestoreMemResumeStateStorage,
+    FirestoreResult,
+};
+use backoff::backoff::Backoff;
+use chrono::prelude::*;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use gcloud_sdk::google::firestore::v1::*;
+use rsb_derive::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::*;
+
+type BoxedErrResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// A consistent view of all documents matching a listener target.
+/// Documents are ordered by their paths.
+#[derive(Debug, Clone, Builder)]
+pub struct FirestoreQuerySnapshot {
+    pub target: FirestoreListenerTarget,
+    pub documents: Vec<Document>,
+    pub changes: Vec<FirestoreQuerySnapshotDocChange>,
+    pub read_time: Option<DateTime<Utc>>,
+}
+
+impl FirestoreQuerySnapshot {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    pub fn obj<T>(&self) -> FirestoreResult<Vec<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        self.documents
+            .iter()
+            .map(FirestoreDb::deserialize_doc_to)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FirestoreQuerySnapshotDocChange {
+    Added(Document),
+    Modified(Document),
+    Removed(String),
+}
+
+impl FirestoreQuerySnapshotDocChange {
+    pub fn document_path(&self) -> &str {
+        match self {
+            FirestoreQuerySnapshotDocChange::Added(doc)
+            | FirestoreQuerySnapshotDocChange::Modified(doc) => doc.name.as_str(),
+            FirestoreQuerySnapshotDocChange::Removed(document_path) => document_path.as_str(),
+        }
+    }
+}
+
+impl FirestoreDb {
+    pub async fn create_snapshot_listener(
+        &self,
+    ) -> FirestoreResult<FirestoreQuerySnapshotListener<FirestoreDb>> {
+        self.create_snapshot_listener_with_params(FirestoreListenerParams::new())
+            .await
+    }
+
+    pub async fn create_snapshot_listener_with_params(
+        &self,
+        params: FirestoreListenerParams,
+    ) -> FirestoreResult<FirestoreQuerySnapshotListener<FirestoreDb>> {
+        FirestoreQuerySnapshotListener::new(self.clone(), params).await
+    }
+}
+
+/// Listener that materializes the full result set of every target in memory
+/// and delivers snapshots at consistent points of the listen stream.
+/// Reconnects and resume tokens are handled by the underlying `FirestoreListener`,
+/// with the resume tokens kept only in memory, since the snapshot state isn't persisted either.
+pub struct FirestoreQuerySnapshotListener<D>
+where
+    D: FirestoreListenSupport,
+{
+    listener: FirestoreListener<D, FirestoreMemResumeStateStorage>,
+    listener_params: FirestoreListenerParams,
+    targets: Vec<FirestoreListenerTargetParams>,
+    shutdown_handle: Option<JoinHandle<()>>,
+    shutdown_writer: Option<Arc<UnboundedSender<i8>>>,
+}
+
+impl<D> FirestoreQuerySnapshotListener<D>
+where
+    D: FirestoreListenSupport + Clone + Send + Sync + 'static,
+{
+    pub async fn new(
+        db: D,
+        listener_params: FirestoreListenerParams,
+    ) -> FirestoreResult<FirestoreQuerySnapshotListener<D>> {
+        Ok(FirestoreQuerySnapshotListener {
+            listener: FirestoreListener::new(
+                db,
+                FirestoreMemResumeStateStorage::new(),
+                listener_params.clone(),
+            )
+            .await?,
+            listener_params,
+            targets: vec![],
+            shutdown_handle: None,
+            shutdown_writer: None,
+        })
+    }
+
+    /// The current status of the listener, including terminal failures
+    /// after the reconnect attempts have been exhausted.
+    pub async fn status(&self) -> FirestoreListenerStatus {
+        self.listener.status().await
+    }
+
+    /// The last error occurred while listening, including the errors the listener recovered from
+    /// and the errors of the callback function.
+    pub async fn last_error(&self) -> Option<String> {
+        self.listener.last_error().await
+    }
+
+    /// Adds a target to listen. Targets have to be added before starting the listener.
+    pub fn add_target(&mut self, target: FirestoreListenerTargetParams) -> FirestoreResult<()> {
+        if self.shutdown_handle.is_some() {
+            return Err(FirestoreError::InvalidParametersError(
+                FirestoreInvalidParametersError::new(FirestoreInvalidParametersPublicDetails::new(
+                    "target".into(),
+                    "Targets can't be added to a running snapshot listener".into(),
+                )),
+            ));
+        }
+
+        self.listener.add_target(target.clone())?;
+        self.targets.push(target);
+        Ok(())
+    }
+
+    /// Starts listening and delivers snapshots to the callback function.
+    /// Failed deliveries are retried with the retry backoff of the listener,
+    /// without reconnecting. When the attempts are exhausted, the changes of the snapshot
+    /// are kept and delivered with the next snapshot.
+    pub async fn start<FN, F>(&mut self, cb: FN) -> FirestoreResult<()>
+    where
+        FN: Fn(FirestoreQuerySnapshot) -> F + Send + Sync + 'static,
+        F: Future<Output = BoxedErrResult<()>> + Send + Sync + 'static,
+    {
+        info!(
+            "Starting a Firestore snapshot listener for targets: {:?}...",
+            &self.targets.len()
+        );
+
+        let targets_state: HashMap<FirestoreListenerTarget, FirestoreQuerySnapshotTargetState> =
+            self.targets
+                .iter()
+                .map(|target_params| {
+                    (
+                        target_params.target.clone(),
+                        FirestoreQuerySnapshotTargetState::new(target_params.clone()),
+                    )
+                })
+                .collect();
+
+        let responses = self.listener.start_responses_stream().await?;
+
+        let (tx, rx): (UnboundedSender<i8>, UnboundedReceiver<i8>) =
+            tokio::sync::mpsc::unbounded_channel();
+
+        self.shutdown_writer = Some(Arc::new(tx));
+        self.shutdown_handle = Some(tokio::spawn(Self::listener_loop(
+            responses,
+            FirestoreQuerySnapshotListenerState {
+                last_error: self.listener.last_error_handle(),
+                target_updates_writer: self.listener.target_updates_writer(),
+                retry_backoff: self.listener_params.effective_retry_backoff(),
+            },
+            targets_state,
+            rx,
+            cb,
+        )));
+        Ok(())
+    }
+
+    pub async fn shutdown(&mut self) -> FirestoreResult<()> {
+        debug!("Shutting down Firestore snapshot listener...");
+        if let Some(shutdown_writer) = self.shutdown_writer.take() {
+            shutdown_writer.send(1).ok();
+        }
+        self.listener.shutdown().await?;
+        if let Some(signaller) = self.shutdown_handle.take() {
+            if let Err(err) = signaller.await {
+                warn!("Firestore snapshot listener exit error: {}...", err);
+            };
+        }
+        debug!("Shutting down Firestore snapshot listener has been finished...");
+        Ok(())
+    }
+
+    async fn listener_loop<FN, F>(
+        mut responses: BoxStream<'static, FirestoreResult<listen_response::ResponseType>>,
+        listener_state: FirestoreQuerySnapshotListenerState,
+        mut targets_state: HashMap<FirestoreListenerTarget, FirestoreQuerySnapshotTargetState>,
+        mut shutdown_receiver: UnboundedReceiver<i8>,
+        cb: FN,
+    ) where
+        FN: Fn(FirestoreQuerySnapshot) -> F + Send + Sync,
+        F: Future<Output = BoxedErrResult<()>> + Send + Sync,
+    {
+        loop {
+            let tried = tokio::select! {
+                _ = shutdown_receiver.recv() => {
+                    debug!("Exiting from snapshot listener...");
+                    shutdown_receiver.close();
+                    break;
+                }
+                tried = responses.next() => tried
+            };
+
+            match tried {
+                Some(Ok(response_type)) => {
+                    match Self::apply_response(&mut targets_state, response_type) {
+                        Ok(FirestoreQuerySnapshotApplied::Snapshots(snapshots)) => {
+                            for snapshot in snapshots {
+                                let target = snapshot.target.clone();
+                                match Self::deliver_snapshot(
+                                    &cb,
+                                    snapshot,
+                                    &listener_state,
+                                    &mut shutdown_receiver,
+                                )
+                                .await
+                                {
+                                    FirestoreQuerySnapshotDelivery::Delivered => {
+                                        if let Some(state) = targets_state.get_mut(&target) {
+                                            state.commit_snapshot();
+                                        }
+                                    }
+                                    // Pending changes are kept until delivered, so they are included
+                                    // in the next snapshot after a failed delivery
+                                    FirestoreQuerySnapshotDelivery::Failed => {}
+                                    FirestoreQuerySnapshotDelivery::Shutdown => return,
+                                }
+                            }
+                        }
+                        Ok(FirestoreQuerySnapshotApplied::Resync(targets)) => {
+                            debug!(
+                                "Existence filter mismatch. Re-syncing targets {:?}...",
+                                targets
+                            );
+                            for target in targets {
+                                if let Some(state) = targets_state.get(&target) {
+                                    listener_state.resync_target(state.params.clone());
+                                }
+                            }
+                        }
+                        Ok(FirestoreQuerySnapshotApplied::Nothing) => {}
+                        Err(err) => {
+                            error!("Snapshot listener event handling error occurred {:?}.", err);
+                            listener_state.set_last_error(err.to_string()).await;
+                        }
+                    }
+                }
+                Some(Err(err)) => {
+                    debug!("Snapshot listener error occurred {:?}.", err);
+                }
+                None => break,
+            }
+        }
+    }
+
+    async fn deliver_snapshot<FN, F>(
+        cb: &FN,
+        snapshot: FirestoreQuerySnapshot,
+        listener_state: &FirestoreQuerySnapshotListenerState,
+        shutdown_receiver: &mut UnboundedReceiver<i8>,
+    ) -> FirestoreQuerySnapshotDelivery
+    where
+        FN: Fn(FirestoreQuerySnapshot) -> F + Send + Sync,
+        F: Future<Output = BoxedErrResult<()>> + Send + Sync,
+    {
+        let mut backoff = listener_state.retry_backoff.create_backoff();
+        let mut failed_attempts: u32 = 0;
+
+        loop {
+            match cb(snapshot.clone()).await {
+                Ok(()) => return FirestoreQuerySnapshotDelivery::Delivered,
+                Err(err) => {
+                    error!(
+                        "Snapshot listener callback function error occurred {:?}.",
+                        err
+                    );
+                    listener_state.set_last_error(err.to_string()).await;
+                    failed_attempts += 1;
+
+                    if listener_state
+                        .retry_backoff
+                        .attempts_exhausted(failed_attempts)
+                    {
+                        warn!(
+                            "Snapshot for target {:?} hasn't been delivered after {} attempts",
+                            snapshot.target, failed_attempts
+                        );
+                        return FirestoreQuerySnapshotDelivery::Failed;
+                    }
+
+                    let effective_delay = backoff
+                        .next_backoff()
+                        .unwrap_or(listener_state.retry_backoff.max_interval);
+                    debug!("Retrying snapshot delivery in {:?}...", effective_delay);
+                    tokio::select! {
+                        _ = shutdown_receiver.recv() => {
+                            shutdown_receiver.close();
+                            return FirestoreQuerySnapshotDelivery::Shutdown;
+                        }
+                        _ = tokio::time::sleep(effective_delay) => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn apply_response(
+        targets_state: &mut HashMap<FirestoreListenerTarget, FirestoreQuerySnapshotTargetState>,
+        response_type: listen_response::ResponseType,
+    ) -> FirestoreResult<FirestoreQuerySnapshotApplied> {
+        match response_type {
+            listen_response::ResponseType::DocumentChange(doc_change) => {
+                if let Some(document) = doc_change.document {
+                    for target_id in &doc_change.removed_target_ids {
+                        if let Some(state) =
+                            targets_state.get_mut(&FirestoreListenerTarget::new(*target_id))
+                        {
+                            state.remove_document(&document.name);
+                        }
+                    }
+                    for target_id in &doc_change.target_ids {
+                        if let Some(state) =
+                            targets_state.get_mut(&FirestoreListenerTarget::new(*target_id))
+                        {
+                            state.upsert_document(document.clone());
+                        }
+                    }
+                }
+                Ok(FirestoreQuerySnapshotApplied::Nothing)
+            }
+            listen_response::ResponseType::DocumentDelete(doc_delete) => {
+                Self::remove_from_targets(
+                    targets_state,
+                    &doc_delete.document,
+                    &doc_delete.removed_target_ids,
+                );
+                Ok(FirestoreQuerySnapshotApplied::Nothing)
+            }
+            listen_response::ResponseType::DocumentRemove(doc_remove) => {
+                Self::remove_from_targets(
+                    targets_state,
+                    &doc_remove.document,
+                    &doc_remove.removed_target_ids,
+                );
+                Ok(FirestoreQuerySnapshotApplied::Nothing)
+            }
+            listen_response::ResponseType::Filter(filter) => {
+                match targets_state.get_mut(&FirestoreListenerTarget::new(filter.target_id)) {
+                    Some(state) if state.documents.len() != filter.count as usize => {
+                        debug!(
+                            "Existence filter mismatch for target {}: {} documents expected, {} known",
+                            filter.target_id,
+                            filter.count,
+                            state.documents.len()
+                        );
+                        state.reset();
+                        state.resyncing = true;
+                        Ok(FirestoreQuerySnapshotApplied::Resync(vec![state
+                            .params
+                            .target
+                            .clone()]))
+                    }
+                    _ => Ok(FirestoreQuerySnapshotApplied::Nothing),
+                }
+            }
+            listen_response::ResponseType::TargetChange(target_change) => {
+                let change_type = target_change.target_change_type();
+                let target_ids: Vec<FirestoreListenerTarget> =
+                    if target_change.target_ids.is_empty() {
+                        targets_state.keys().cloned().collect()
+                    } else {
+                        target_change
+                            .target_ids
+                            .iter()
+                            .map(|target_id| FirestoreListenerTarget::new(*target_id))
+                            .collect()
+                    };
+
+                match change_type {
+                    target_change::TargetChangeType::NoChange
+                        if target_change.target_ids.is_empty()
+                            && target_change.read_time.is_some() =>
+                    {
+                        let read_time = target_change.read_time.map(from_timestamp).transpose()?;
+                        Ok(FirestoreQuerySnapshotApplied::Snapshots(
+                            targets_state
+                                .values()
+                                .filter_map(|state| state.snapshot(read_time))
+                                .collect(),
+                        ))
+                    }
+                    target_change::TargetChangeType::NoChange
+                    | target_change::TargetChangeType::Add => {
+                        Ok(FirestoreQuerySnapshotApplied::Nothing)
+                    }
+                    target_change::TargetChangeType::Current => {
+                        for target in &target_ids {
+                            if let Some(state) = targets_state.get_mut(target) {
+                                state.current = true;
+                            }
+                        }
+                        Ok(FirestoreQuerySnapshotApplied::Nothing)
+                    }
+                    target_change::TargetChangeType::Reset => {
+                        for target in &target_ids {
+                            if let Some(state) = targets_state.get_mut(target) {
+                                state.reset();
+                            }
+                        }
+                        Ok(FirestoreQuerySnapshotApplied::Nothing)
+                    }
+                    target_change::TargetChangeType::Remove => {
+                        debug!(
+                            "Targets {:?} have been removed: {:?}",
+                            target_ids, target_change.cause
+                        );
+                        for target in &target_ids {
+                            match targets_state.get_mut(target) {
+                                // Removal of a re-synced target requested by the listener itself
+                                Some(state) if state.resyncing => {
+                                    state.resyncing = false;
+                                    state.reset();
+                                }
+                                _ => {
+                                    targets_state.remove(target);
+                                }
+                            }
+                        }
+                        Ok(FirestoreQuerySnapshotApplied::Nothing)
+                    }
+                }
+            }
+        }
+    }
+
+    fn remove_from_targets(
+        targets_state: &mut HashMap<FirestoreListenerTarget, FirestoreQuerySnapshotTargetState>,
+        document_name: &str,
+        removed_target_ids: &[i32],
+    ) {
+        if removed_target_ids.is_empty() {
+            for state in targets_state.values_mut() {
+                state.remove_document(document_name);
+            }
+        } else {
+            for target_id in removed_target_ids {
+                if let Some(state) =
+                    targets_state.get_mut(&FirestoreListenerTarget::new(*target_id))
+                {
+                    state.remove_document(document_name);
+                }
+            }
+        }
+    }
+}
+
+// Handles shared with the underlying listener
+struct FirestoreQuerySnapshotListenerState {
+    last_error: Arc<RwLock<Option<String>>>,
+    target_updates_writer: Option<UnboundedSender<FirestoreListenerTargetUpdate>>,
+    retry_backoff: FirestoreListenerRetryBackoff,
+}
+
+impl FirestoreQuerySnapshotListenerState {
+    async fn set_last_error(&self, err: String) {
+        *self.last_error.write().await = Some(err);
+    }
+
+    // Re-adding a removed target drops its resume token, so the server sends its documents again
+    fn resync_target(&self, target_params: FirestoreListenerTargetParams) {
+        if let Some(ref target_updates_writer) = self.target_updates_writer {
+            target_updates_writer
+                .send(FirestoreListenerTargetUpdate::Remove(
+                    target_params.target.clone(),
+                ))
+                .ok();
+            target_updates_writer
+                .send(FirestoreListenerTargetUpdate::Add(target_params))
+                .ok();
+        }
+    }
+}
+
+enum FirestoreQuerySnapshotApplied {
+    Nothing,
+    Snapshots(Vec<FirestoreQuerySnapshot>),
+    Resync(Vec<FirestoreListenerTarget>),
+}
+
+enum FirestoreQuerySnapshotDelivery {
+    Delivered,
+    Failed,
+    Shutdown,
+}
+
+struct FirestoreQuerySnapshotTargetState {
+    params: FirestoreListenerTargetParams,
+    current: bool,
+    snapshot_emitted: bool,
+    // Waiting for the removal of the target before it is listened again from scratch
+    resyncing: bool,
+    documents: BTreeMap<String, Document>,
+    // Documents changed since the last snapshot, with their existence in that snapshot
+    pending_changes: BTreeMap<String, bool>,
+}
+
+impl FirestoreQuerySnapshotTargetState {
+    fn new(params: FirestoreListenerTargetParams) -> Self {
+        Self {
+            params,
+            current: false,
+            snapshot_emitted: false,
+            resyncing: false,
+            documents: BTreeMap::new(),
+            pending_changes: BTreeMap::new(),
+        }
+    }
+
+    fn upsert_document(&mut self, document: Document) {
+        self.track_change(&document.name);
+        self.documents.insert(document.name.clone(), document);
+    }
+
+    fn remove_document(&mut self, document_name: &str) {
+        if self.documents.contains_key(document_name) {
+            self.track_change(document_name);
+            self.documents.remove(document_name);
+        }
+    }
+
+    fn track_change(&mut self, document_name: &str) {
+        if !self.pending_changes.contains_key(document_name) {
+            self.pending_changes.insert(
+                document_name.to_string(),
+                self.documents.contains_key(document_name),
+            );
+        }
+    }
+
+    fn reset(&mut self) {
+        let document_names: Vec<String> = self.documents.keys().cloned().collect();
+        for document_name in document_names {
+            self.remove_document(&document_name);
+        }
+        self.current = false;
+    }
+
+    /// Builds a snapshot without consuming the pending changes:
+    /// they are cleared with `commit_snapshot` once the snapshot has been delivered.
+    fn snapshot(&self, read_time: Option<DateTime<Utc>>) -> Option<FirestoreQuerySnapshot> {
+        if !self.current || (self.snapshot_emitted && self.pending_changes.is_empty()) {
+            return None;
+        }
+
+        let changes: Vec<FirestoreQuerySnapshotDocChange> = self
+            .pending_changes
+            .iter()
+            .filter_map(|(document_name, existed)| {
+                match (existed, self.documents.get(document_name)) {
+                    (false, Some(doc)) => Some(FirestoreQuerySnapshotDocChange::Added(doc.clone())),
+                    (true, Some(doc)) => {
+                        Some(FirestoreQuerySnapshotDocChange::Modified(doc.clone()))
+                    }
+                    (true, None) => Some(FirestoreQuerySnapshotDocChange::Removed(
+                        document_name.clone(),
+                    )),
+                    (false, None) => None,
+                }
+            })
+            .collect();
+
+        if self.snapshot_emitted && changes.is_empty() {
+            return None;
+        }
+
+        Some(
+            FirestoreQuerySnapshot::new(
+                self.params.target.clone(),
+                self.documents.values().cloned().collect(),
+                changes,
+            )
+            .opt_read_time(read_time),
+        )
+    }
+
+    fn commit_snapshot(&mut self) {
+        self.pending_changes.clear();
+        self.snapshot_emitted = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FirestoreQueryParams, FirestoreTargetType};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone)]
+    struct FailingListenDatabase;
+
+    #[async_trait]
+    impl FirestoreListenSupport for FailingListenDatabase {
+        async fn listen_doc_changes<'a, 'b>(
+            &'a self,
+            _targets: Vec<FirestoreListenerTargetParams>,
+        ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
+            Err(FirestoreError::SystemError(FirestoreSystemError::new(
+                FirestoreErrorPublicGenericDetails::new("SystemError".into()),
+                "Listen test error".into(),
+            )))
+        }
+
+        async fn listen_doc_changes_with_updates<'a, 'b>(
+            &'a self,
+            targets: Vec<FirestoreListenerTargetParams>,
+            _target_updates: UnboundedReceiver<FirestoreListenerTargetUpdate>,
+        ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
+            self.listen_doc_changes(targets).await
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct ScriptedListenDatabase {
+        connects: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl FirestoreListenSupport for ScriptedListenDatabase {
+        async fn listen_doc_changes<'a, 'b>(
+            &'a self,
+            _targets: Vec<FirestoreListenerTargetParams>,
+        ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
+            self.connects.fetch_add(1, Ordering::Relaxed);
+            let responses = vec![
+                listen_response::ResponseType::DocumentChange(DocumentChange {
+                    document: Some(doc("doc1")),
+                    target_ids: vec![1],
+                    removed_target_ids: vec![],
+                }),
+                listen_response::ResponseType::TargetChange(TargetChange {
+                    target_change_type: target_change::TargetChangeType::Current as i32,
+                    target_ids: vec![1],
+                    cause: None,
+                    resume_token: vec![],
+                    read_time: None,
+                }),
+                listen_response::ResponseType::TargetChange(TargetChange {
+                    target_change_type: target_change::TargetChangeType::NoChange as i32,
+                    target_ids: vec![],
+                    cause: None,
+                    resume_token: vec![1],
+                    read_time: Some(prost_types::Timestamp {
+                        seconds: 1_700_000_000,
+                        nanos: 0,
+                    }),
+                }),
+            ];
+            Ok(
+                futures::stream::iter(responses.into_iter().map(|response_type| {
+                    Ok(ListenResponse {
+                        response_type: Some(response_type),
+                    })
+                }))
+                .chain(futures::stream::pending())
+                .boxed(),
+            )
+        }
+
+        async fn listen_doc_changes_with_updates<'a, 'b>(
+            &'a self,
+            targets: Vec<FirestoreListenerTargetParams>,
+            _target_updates: UnboundedReceiver<FirestoreListenerTargetUpdate>,
+        ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
+            self.listen_doc_changes(targets).await
+        }
+    }
+
+    fn target_params(target_id: i32) -> FirestoreListenerTargetParams {
+        FirestoreListenerTargetParams::new(
+            FirestoreListenerTarget::new(target_id),
+            FirestoreTargetType::Query(FirestoreQueryParams::new("test".into())),
+            HashMap::new(),
+        )
+    }
+
+    fn doc(name: &str) -> Document {
+        Document {
+            name: name.to_string(),
+            fields: HashMap::new(),
+            create_time: None,
+            update_time: None,
+        }
+    }
+
+    #[test]
+    fn snapshot_target_state_diffs() {
+        let mut state = FirestoreQuerySnapshotTargetState::new(target_params(1));
+
+        state.upsert_document(doc("doc1"));
+        state.upsert_document(doc("doc2"));
+        assert!(state.snapshot(None).is_none());
+
+        state.current = true;
+        let snapshot = state.snapshot(None).unwrap();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot.changes.len(), 2);
+        state.commit_snapshot();
+
+        state.remove_document("doc1");
+        state.upsert_document(doc("doc2"));
+        state.upsert_document(doc("doc3"));
+        state.upsert_document(doc("doc4"));
+        state.remove_document("doc4");
+
+        // Not delivered snapshots keep the changes for the next one
+        assert_eq!(state.snapshot(None).unwrap().changes.len(), 3);
+        state.upsert_document(doc("doc5"));
+        state.remove_document("doc5");
+
+        let snapshot = state.snapshot(None).unwrap();
+        assert_eq!(snapshot.len(), 2);
+        assert!(matches!(
+            snapshot.changes.as_slice(),
+            [
+                FirestoreQuerySnapshotDocChange::Removed(_),
+                FirestoreQuerySnapshotDocChange::Modified(_),
+                FirestoreQuerySnapshotDocChange::Added(_)
+            ]
+        ));
+        state.commit_snapshot();
+
+        assert!(state.snapshot(None).is_none());
+    }
+
+    #[tokio::test]
+    async fn snapshot_listener_fails_after_attempts() {
+        let mut listener = FirestoreQuerySnapshotListener::new(
+            FailingListenDatabase,
+            FirestoreListenerParams::new().with_retry_backoff(
+                FirestoreListenerRetryBackoff::new()
+                    .with_initial_interval(std::time::Duration::from_millis(1))
+                    .with_max_interval(std::time::Duration::from_millis(1))
+                    .with_max_attempts(2),
+            ),
+        )
+        .await
+        .unwrap();
+
+        listener.start(|_| async move { Ok(()) }).await.unwrap();
+        listener.shutdown_handle.take().unwrap().await.unwrap();
+
+        assert!(matches!(
+            listener.status().await,
+            FirestoreListenerStatus::Failed(err) if err.contains("Listen test error")
+        ));
+        assert!(listener.last_error().await.is_some());
+
+        listener.shutdown().await.unwrap();
+        assert!(matches!(
+            listener.status().await,
+            FirestoreListenerStatus::Failed(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn snapshot_listener_rejects_duplicate_targets() {
+        let mut listener = FirestoreQuerySnapshotListener::new(
+            ScriptedListenDatabase::default(),
+            FirestoreListenerParams::new(),
+        )
+        .await
+        .unwrap();
+
+        assert!(listener.add_target(target_params(1)).is_ok());
+        assert!(listener.add_target(target_params(1)).is_err());
+        assert_eq!(listener.targets.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn snapshot_listener_retries_callback_without_reconnecting() {
+        let db = ScriptedListenDatabase::default();
+        let mut listener = FirestoreQuerySnapshotListener::new(
+            db.clone(),
+            FirestoreListenerParams::new().with_retry_backoff(
+                FirestoreListenerRetryBackoff::new()
+                    .with_initial_interval(std::time::Duration::from_millis(1))
+                    .with_max_interval(std::time::Duration::from_millis(1))
+                    .with_max_attempts(3),
+            ),
+        )
+        .await
+        .unwrap();
+        listener.add_target(target_params(1)).unwrap();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (delivered_writer, mut delivered_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let cb_calls = calls.clone();
+        listener
+            .start(move |snapshot| {
+                let call = cb_calls.fetch_add(1, Ordering::Relaxed);
+                let delivered_writer = delivered_writer.clone();
+                async move {
+                    if call < 2 {
+                        Err("Callback test error".into())
+                    } else {
+                        delivered_writer.send(snapshot).ok();
+                        Ok(())
+                    }
+                }
+            })
+            .await
+            .unwrap();
+
+        let snapshot =
+            tokio::time::timeout(std::time::Duration::from_secs(1), delivered_receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot.changes.len(), 1);
+        assert!(snapshot.read_time.is_some());
+
+        assert_eq!(db.connects.load(Ordering::Relaxed), 1);
+        assert_eq!(listener.status().await, FirestoreListenerStatus::Listening);
+        assert!(matches!(
+            listener.last_error().await,
+            Some(err) if err.contains("Callback test error")
+        ));
+
+        listener.shutdown().await.unwrap();
+        assert_eq!(listener.status().await, FirestoreListenerStatus::Stopped);
+    }
+}
@@ -29,6 +29,15 @@ pub use list_doc::*;
 mod listen_changes;
 pub use listen_changes::*;
 
+mod listen_changes_models;
+pub use listen_changes_models::*;
+
+mod listen_snapshots;
+pub use listen_snapshots::*;
+
+mod listen_resume_storages;
+pub use listen_resume_storages::*;
+
 use crate::FirestoreResult;
 use gcloud_sdk::google::firestore::v1::firestore_client::FirestoreClient;
 use gcloud_sdk::google::firestore::v1::*;
@@ -65,6 +74,9 @@ pub use batch_streaming_writer::*;
 mod batch_simple_writer;
 pub use batch_simple_writer::*;
 
+mod bulk_writer;
+pub use bulk_writer::*;
+
 use crate::errors::{
     FirestoreError, FirestoreInvalidParametersError, FirestoreInvalidParametersPublicDetails,
 };
@@ -84,6 +96,24 @@ pub struct FirestoreDb {
 
 const GOOGLE_FIREBASE_API_URL: &str = "https://firestore.googleapis.com";
 const GOOGLE_FIRESTORE_EMULATOR_HOST_ENV: &str = "FIRESTORE_EMULATOR_HOST";
+const GOOGLE_CLOUD_RESOURCE_PREFIX_HEADER: &str = "google-cloud-resource-prefix";
+const GOOGLE_REQUEST_PARAMS_HEADER: &str = "x-goog-request-params";
+
+// Characters escaped in the request params header values, as in URL query components
+const GOOGLE_REQUEST_PARAMS_ENCODE_SET: &percent_encoding::AsciiSet =
+    &percent_encoding::NON_ALPHANUMERIC
+        .remove(b'-')
+        .remove(b'.')
+        .remove(b'_')
+        .remove(b'~');
+
+fn request_params_header_value(project_id: &str, database_id: &str) -> String {
+    format!(
+        "project_id={}&database_id={}",
+        percent_encoding::utf8_percent_encode(project_id, GOOGLE_REQUEST_PARAMS_ENCODE_SET),
+        percent_encoding::utf8_percent_encode(database_id, GOOGLE_REQUEST_PARAMS_ENCODE_SET)
+    )
+}
 
 impl FirestoreDb {
     pub async fn new<S>(google_project_id: S) -> FirestoreResult<Self>
@@ -110,8 +140,7 @@ impl FirestoreDb {
         token_scopes: Vec<String>,
         token_source_type: TokenSourceType,
     ) -> FirestoreResult<Self> {
-        let firestore_database_path =
-            format!("projects/{}/databases/(default)", options.google_project_id);
+        let firestore_database_path = Self::create_database_path(&options);
         let firestore_database_doc_path = format!("{}/documents", firestore_database_path);
 
         let effective_firebase_api_url = options
@@ -127,10 +156,12 @@ impl FirestoreDb {
             token_scopes.join(", ")
         );
 
+        // The resource prefix and routing headers are attached to every request instead of the client,
+        // so the same client can be shared between databases (see `clone_with_database_id`).
         let client = GoogleApiClient::from_function_with_token_source(
             FirestoreClient::new,
             effective_firebase_api_url,
-            Some(firestore_database_path.clone()),
+            None,
             token_scopes,
             token_source_type,
         )
@@ -145,6 +176,13 @@ impl FirestoreDb {
         })
     }
 
+    fn create_database_path(options: &FirestoreDbOptions) -> String {
+        format!(
+            "projects/{}/databases/{}",
+            options.google_project_id, options.database_id
+        )
+    }
+
     pub fn deserialize_doc_to<T>(doc: &Document) -> FirestoreResult<T>
     where
         for<'de> T: Deserialize<'de>,
@@ -224,6 +262,39 @@ impl FirestoreDb {
         }
     }
 
+    pub fn clone_with_database_id<S>(&self, database_id: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        let options = self
+            .options
+            .clone()
+            .with_database_id(database_id.as_ref().to_string());
+        let database_path = Self::create_database_path(&options);
+        let doc_path = format!("{}/documents", database_path);
+        Self {
+            database_path,
+            doc_path,
+            options,
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn create_request<T>(&self, message: T) -> FirestoreResult<tonic::Request<T>> {
+        let mut request = tonic::Request::new(message);
+        let metadata = request.metadata_mut();
+        metadata.insert(
+            GOOGLE_CLOUD_RESOURCE_PREFIX_HEADER,
+            self.database_path.parse()?,
+        );
+        metadata.insert(
+            GOOGLE_REQUEST_PARAMS_HEADER,
+            request_params_header_value(&self.options.google_project_id, &self.options.database_id)
+                .parse()?,
+        );
+        Ok(request)
+    }
+
     #[inline]
     pub fn clone_with_consistency_selector(
         &self,
@@ -270,3 +341,20 @@ where
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_request_params_header() {
+        assert_eq!(
+            request_params_header_value("test-project", "(default)"),
+            "project_id=test-project&database_id=%28default%29"
+        );
+        assert_eq!(
+            request_params_header_value("test-project", "my db&x=1"),
+            "project_id=test-project&database_id=my%20db%26x%3D1"
+        );
+    }
+}
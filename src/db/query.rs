@@ -75,7 +75,8 @@ impl FirestoreDb {
         &self,
         params: &FirestoreQueryParams,
     ) -> FirestoreResult<tonic::Request<RunQueryRequest>> {
-        Ok(tonic::Request::new(RunQueryRequest {
+        params.validate()?;
+        self.create_request(RunQueryRequest {
             parent: params
                 .parent
                 .as_ref()
@@ -88,7 +89,7 @@ impl FirestoreDb {
                 .map(|selector| selector.try_into())
                 .transpose()?,
             query_type: Some(run_query_request::QueryType::StructuredQuery(params.into())),
-        }))
+        })
     }
 
     fn stream_query_doc_with_retries<'a, 'b>(
@@ -335,6 +336,8 @@ impl FirestoreQuerySupport for FirestoreDb {
         params: FirestorePartitionQueryParams,
     ) -> BoxFuture<FirestoreResult<PeekableBoxStream<FirestoreResult<FirestoreQueryCursor>>>> {
         Box::pin(async move {
+            params.query_params.validate()?;
+
             let consistency_selector: Option<
                 gcloud_sdk::google::firestore::v1::partition_query_request::ConsistencySelector,
             > = self
@@ -349,7 +352,7 @@ impl FirestoreQuerySupport for FirestoreDb {
                     Some((params, consistency_selector)),
                     move |maybe_params| async move {
                         if let Some((params, maybe_consistency_selector)) = maybe_params {
-                            let request = tonic::Request::new(PartitionQueryRequest {
+                            let request = match self.create_request(PartitionQueryRequest {
                                 page_size: params.page_size as i32,
                                 partition_count: params.partition_count as i64,
                                 parent: params
@@ -365,7 +368,10 @@ impl FirestoreQuerySupport for FirestoreDb {
                                     ),
                                 ),
                                 page_token: params.page_token.clone().unwrap_or_default(),
-                            });
+                            }) {
+                                Ok(request) => request,
+                                Err(err) => return Some((Err(err), None)),
+                            };
 
                             match self.client().get().partition_query(request).await {
                                 Ok(response) => {
@@ -1,9 +1,15 @@
 #![allow(clippy::derive_partial_eq_without_eq)] // Since we may not be able to implement Eq for the changes coming from Firestore protos
 
-use crate::FirestoreValue;
+use crate::errors::{
+    FirestoreError, FirestoreInvalidParametersError, FirestoreInvalidParametersPublicDetails,
+};
+use crate::{FirestoreResult, FirestoreValue};
 use gcloud_sdk::google::firestore::v1::*;
 use rsb_derive::Builder;
 
+// https://firebase.google.com/docs/firestore/query-data/queries#limits_on_or_queries
+const FIRESTORE_MAX_DISJUNCTIONS: usize = 30;
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub enum FirestoreQueryCollection {
     Single(String),
@@ -40,6 +46,13 @@ pub struct FirestoreQueryParams {
 }
 
 impl FirestoreQueryParams {
+    pub fn validate(&self) -> FirestoreResult<()> {
+        self.filter
+            .as_ref()
+            .map(|filter| filter.validate())
+            .unwrap_or(Ok(()))
+    }
+
     pub fn to_structured_query(&self) -> StructuredQuery {
         let query_filter = self.filter.as_ref().map(|f| f.to_structured_query_filter());
 
@@ -91,6 +104,57 @@ pub enum FirestoreQueryFilter {
 }
 
 impl FirestoreQueryFilter {
+    /// Checks the server limits for disjunctions locally, so too large filters are rejected before sending a request.
+    pub fn validate(&self) -> FirestoreResult<()> {
+        let disjunctions = self.disjunctions_count().unwrap_or(0);
+        if disjunctions > FIRESTORE_MAX_DISJUNCTIONS {
+            Err(FirestoreError::InvalidParametersError(
+                FirestoreInvalidParametersError::new(FirestoreInvalidParametersPublicDetails::new(
+                    "filter".to_string(),
+                    format!(
+                        "Filter has {} disjunctions in disjunctive normal form, while the maximum allowed is {}",
+                        disjunctions, FIRESTORE_MAX_DISJUNCTIONS
+                    ),
+                )),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Number of disjunctions when the filter is converted to disjunctive normal form.
+    /// `None` means that filter is empty and ignored.
+    fn disjunctions_count(&self) -> Option<usize> {
+        match self {
+            FirestoreQueryFilter::Compare(None) => None,
+            FirestoreQueryFilter::Compare(Some(
+                FirestoreQueryFilterCompare::In(_, fvalue)
+                | FirestoreQueryFilterCompare::ArrayContainsAny(_, fvalue),
+            )) => match fvalue.value.value_type {
+                Some(value::ValueType::ArrayValue(ref array_value)) => {
+                    Some(array_value.values.len().max(1))
+                }
+                _ => Some(1),
+            },
+            FirestoreQueryFilter::Compare(Some(_)) | FirestoreQueryFilter::Unary(_) => Some(1),
+            FirestoreQueryFilter::Composite(composite) => {
+                let counts = composite
+                    .for_all_filters
+                    .iter()
+                    .filter_map(|filter| filter.disjunctions_count());
+                match composite.operator {
+                    FirestoreQueryFilterCompositeOperator::And => counts
+                        .fold(None, |acc, count| {
+                            Some(acc.unwrap_or(1).saturating_mul(count))
+                        }),
+                    FirestoreQueryFilterCompositeOperator::Or => counts.fold(None, |acc, count| {
+                        Some(acc.unwrap_or(0).saturating_add(count))
+                    }),
+                }
+            }
+        }
+    }
+
     fn to_structured_query_filter(&self) -> structured_query::Filter {
         let filter_type = match self {
             FirestoreQueryFilter::Compare(comp) => comp.as_ref().map(|cmp| {
@@ -190,7 +254,14 @@ impl FirestoreQueryFilter {
             FirestoreQueryFilter::Composite(composite) => {
                 Some(structured_query::filter::FilterType::CompositeFilter(
                     structured_query::CompositeFilter {
-                        op: structured_query::composite_filter::Operator::And.into(),
+                        op: (match composite.operator {
+                            FirestoreQueryFilterCompositeOperator::And => {
+                                structured_query::composite_filter::Operator::And.into()
+                            }
+                            FirestoreQueryFilterCompositeOperator::Or => {
+                                structured_query::composite_filter::Operator::Or.into()
+                            }
+                        }),
                         filters: composite
                             .for_all_filters
                             .iter()
@@ -321,6 +392,21 @@ impl ToString for FirestoreQueryDirection {
 #[derive(Debug, PartialEq, Clone, Builder)]
 pub struct FirestoreQueryFilterComposite {
     pub for_all_filters: Vec<FirestoreQueryFilter>,
+    #[default = "FirestoreQueryFilterCompositeOperator::And"]
+    pub operator: FirestoreQueryFilterCompositeOperator,
+}
+
+impl FirestoreQueryFilterComposite {
+    /// Creates a composite filter matching documents that satisfy any of the filters.
+    pub fn new_or(filters: Vec<FirestoreQueryFilter>) -> Self {
+        Self::new(filters).with_operator(FirestoreQueryFilterCompositeOperator::Or)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum FirestoreQueryFilterCompositeOperator {
+    And,
+    Or,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
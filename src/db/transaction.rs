@@ -1,13 +1,23 @@
+use crate::db::batch_writer::check_batch_writes_limits;
+use crate::db::safe_document_path;
+use crate::db::transaction_ops::{is_create_write, write_document_path};
+use crate::errors::*;
 use crate::timestamp_utils::from_timestamp;
 use crate::{
-    FirestoreConsistencySelector, FirestoreDb, FirestoreError, FirestoreResult,
+    FirestoreAggregatedQueryParams, FirestoreConsistencySelector, FirestoreDb, FirestoreError,
+    FirestoreGetByIdSupport, FirestoreQueryParams, FirestoreQuerySupport, FirestoreResult,
     FirestoreTransactionId, FirestoreTransactionMode, FirestoreTransactionOptions,
-    FirestoreTransactionResponse, FirestoreWriteResult,
+    FirestoreTransactionResponse, FirestoreTransactionRetryPolicy, FirestoreWriteResult,
 };
-use backoff::future::retry;
-use backoff::ExponentialBackoffBuilder;
+use async_trait::async_trait;
+use backoff::backoff::Backoff;
 use futures::future::BoxFuture;
-use gcloud_sdk::google::firestore::v1::{BeginTransactionRequest, CommitRequest, RollbackRequest};
+use futures::StreamExt;
+use gcloud_sdk::google::firestore::v1::{
+    BeginTransactionRequest, CommitRequest, Document, RollbackRequest, Write,
+};
+use serde::Deserialize;
+use std::collections::HashSet;
 use tracing::*;
 
 pub struct FirestoreTransaction<'a> {
@@ -15,6 +25,9 @@ pub struct FirestoreTransaction<'a> {
     pub transaction_id: FirestoreTransactionId,
     pub transaction_span: Span,
     writes: Vec<gcloud_sdk::google::firestore::v1::Write>,
+    read_db: FirestoreDb,
+    read_documents: HashSet<String>,
+    strict_reads: bool,
     finished: bool,
 }
 
@@ -30,10 +43,10 @@ impl<'a> FirestoreTransaction<'a> {
             "/firestore/commit_time" = field::Empty
         );
 
-        let request = tonic::Request::new(BeginTransactionRequest {
+        let request = db.create_request(BeginTransactionRequest {
             database: db.get_database_path().clone(),
             options: Some(options.clone().try_into()?),
-        });
+        })?;
 
         let response = db
             .client()
@@ -51,11 +64,18 @@ impl<'a> FirestoreTransaction<'a> {
             debug!("Created a new transaction. Mode: {:?}", options.mode);
         });
 
+        let read_db = db.clone_with_consistency_selector(
+            FirestoreConsistencySelector::Transaction(response.transaction.clone()),
+        );
+
         Ok(Self {
             db,
             transaction_id: response.transaction,
             transaction_span,
             writes: Vec::new(),
+            read_db,
+            read_documents: HashSet::new(),
+            strict_reads: options.strict_reads,
             finished: false,
         })
     }
@@ -65,6 +85,13 @@ impl<'a> FirestoreTransaction<'a> {
         &self.transaction_id
     }
 
+    /// Full paths of the documents read in this transaction so far,
+    /// including the documents requested by id which don't exist.
+    #[inline]
+    pub fn read_documents(&self) -> &HashSet<String> {
+        &self.read_documents
+    }
+
     #[inline]
     pub fn add<I>(&mut self, write: I) -> FirestoreResult<&mut Self>
     where
@@ -75,13 +102,32 @@ impl<'a> FirestoreTransaction<'a> {
     }
 
     pub async fn commit(mut self) -> FirestoreResult<FirestoreTransactionResponse> {
+        if self.strict_reads {
+            if let Err(err) = self.check_writes_were_read() {
+                self.transaction_span.in_scope(|| {
+                    warn!("{}. Rolling back the transaction", err);
+                });
+                self.rollback().await.ok();
+                return Err(err);
+            }
+        }
+
+        // Commits are atomic, so the writes exceeding the limits can't be split
+        if let Err(err) = check_batch_writes_limits(&self.writes) {
+            self.transaction_span.in_scope(|| {
+                warn!("{}. Rolling back the transaction", err);
+            });
+            self.rollback().await.ok();
+            return Err(err);
+        }
+
         self.finished = true;
 
-        let request = tonic::Request::new(CommitRequest {
+        let request = self.db.create_request(CommitRequest {
             database: self.db.get_database_path().clone(),
             writes: self.writes.drain(..).collect(),
             transaction: self.transaction_id.clone(),
-        });
+        })?;
 
         let response = self.db.client().get().commit(request).await?.into_inner();
 
@@ -108,10 +154,10 @@ impl<'a> FirestoreTransaction<'a> {
 
     pub async fn rollback(mut self) -> FirestoreResult<()> {
         self.finished = true;
-        let request = tonic::Request::new(RollbackRequest {
+        let request = self.db.create_request(RollbackRequest {
             database: self.db.get_database_path().clone(),
             transaction: self.transaction_id.clone(),
-        });
+        })?;
 
         self.db.client().get().rollback(request).await?;
 
@@ -123,6 +169,189 @@ impl<'a> FirestoreTransaction<'a> {
     }
 }
 
+impl<'a> FirestoreTransaction<'a> {
+    pub async fn get_obj<T, S>(&mut self, collection_id: &str, document_id: S) -> FirestoreResult<T>
+    where
+        for<'de> T: Deserialize<'de>,
+        S: AsRef<str> + Send,
+    {
+        let parent = self.db.get_documents_path().clone();
+        self.get_obj_at(parent.as_str(), collection_id, document_id)
+            .await
+    }
+
+    pub async fn get_obj_at<T, S>(
+        &mut self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+    ) -> FirestoreResult<T>
+    where
+        for<'de> T: Deserialize<'de>,
+        S: AsRef<str> + Send,
+    {
+        self.read_documents.insert(safe_document_path(
+            parent,
+            collection_id,
+            document_id.as_ref(),
+        )?);
+        self.read_db
+            .get_obj_at(parent, collection_id, document_id)
+            .await
+    }
+
+    pub async fn get_obj_if_exists<T, S>(
+        &mut self,
+        collection_id: &str,
+        document_id: S,
+    ) -> FirestoreResult<Option<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+        S: AsRef<str> + Send,
+    {
+        let parent = self.db.get_documents_path().clone();
+        self.get_obj_at_if_exists(parent.as_str(), collection_id, document_id)
+            .await
+    }
+
+    pub async fn get_obj_at_if_exists<T, S>(
+        &mut self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+    ) -> FirestoreResult<Option<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+        S: AsRef<str> + Send,
+    {
+        self.read_documents.insert(safe_document_path(
+            parent,
+            collection_id,
+            document_id.as_ref(),
+        )?);
+        self.read_db
+            .get_obj_at_if_exists(parent, collection_id, document_id, None)
+            .await
+    }
+
+    pub async fn batch_get_obj<T, S, I>(
+        &mut self,
+        collection_id: &str,
+        document_ids: I,
+    ) -> FirestoreResult<Vec<(String, Option<T>)>>
+    where
+        for<'de> T: Deserialize<'de> + Send,
+        S: AsRef<str> + Send,
+        I: IntoIterator<Item = S> + Send,
+    {
+        let parent = self.db.get_documents_path().clone();
+        self.batch_get_obj_at(parent.as_str(), collection_id, document_ids)
+            .await
+    }
+
+    pub async fn batch_get_obj_at<T, S, I>(
+        &mut self,
+        parent: &str,
+        collection_id: &str,
+        document_ids: I,
+    ) -> FirestoreResult<Vec<(String, Option<T>)>>
+    where
+        for<'de> T: Deserialize<'de> + Send,
+        S: AsRef<str> + Send,
+        I: IntoIterator<Item = S> + Send,
+    {
+        let document_ids: Vec<String> = document_ids
+            .into_iter()
+            .map(|document_id| document_id.as_ref().to_string())
+            .collect();
+
+        for document_id in document_ids.iter() {
+            self.read_documents.insert(safe_document_path(
+                parent,
+                collection_id,
+                document_id.as_str(),
+            )?);
+        }
+
+        let docs: Vec<FirestoreResult<(String, Option<Document>)>> = self
+            .read_db
+            .batch_stream_get_docs_at_with_errors(parent, collection_id, document_ids, None)
+            .await?
+            .collect()
+            .await;
+
+        docs.into_iter()
+            .map(|doc_result| {
+                let (document_id, maybe_doc) = doc_result?;
+                Ok((
+                    document_id,
+                    maybe_doc
+                        .as_ref()
+                        .map(FirestoreDb::deserialize_doc_to)
+                        .transpose()?,
+                ))
+            })
+            .collect()
+    }
+
+    pub async fn query_doc(
+        &mut self,
+        params: FirestoreQueryParams,
+    ) -> FirestoreResult<Vec<Document>> {
+        let docs = self.read_db.query_doc(params).await?;
+        self.read_documents
+            .extend(docs.iter().map(|doc| doc.name.clone()));
+        Ok(docs)
+    }
+
+    pub async fn query_obj<T>(&mut self, params: FirestoreQueryParams) -> FirestoreResult<Vec<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        self.query_doc(params)
+            .await?
+            .iter()
+            .map(FirestoreDb::deserialize_doc_to)
+            .collect()
+    }
+
+    pub async fn aggregated_query_obj<T>(
+        &mut self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        self.read_db.aggregated_query_obj(params).await
+    }
+
+    fn check_writes_were_read(&self) -> FirestoreResult<()> {
+        match Self::find_unread_write(&self.writes, &self.read_documents) {
+            Some(document_path) => Err(FirestoreError::InvalidParametersError(
+                FirestoreInvalidParametersError::new(FirestoreInvalidParametersPublicDetails::new(
+                    "writes".into(),
+                    format!(
+                        "Document {} is written in a strict transaction without being read",
+                        document_path
+                    ),
+                )),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn find_unread_write<'w>(
+        writes: &'w [Write],
+        read_documents: &HashSet<String>,
+    ) -> Option<&'w str> {
+        writes
+            .iter()
+            .filter(|write| !is_create_write(write))
+            .filter_map(write_document_path)
+            .find(|document_path| !read_documents.contains(*document_path))
+    }
+}
+
 impl<'a> Drop for FirestoreTransaction<'a> {
     fn drop(&mut self) {
         if !self.finished {
@@ -132,6 +361,59 @@ impl<'a> Drop for FirestoreTransaction<'a> {
     }
 }
 
+/// Transaction function with an async method, an alternative to the closures for `run_transaction`.
+/// It may hold any captured state and may be called more than once when the transaction is retried.
+#[async_trait]
+pub trait FirestoreTransactionFn<T> {
+    async fn run(
+        &self,
+        db: FirestoreDb,
+        transaction: &mut FirestoreTransaction<'_>,
+    ) -> FirestoreResult<T>;
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FirestoreTransactionRunResult<T> {
+    pub value: T,
+    pub response: FirestoreTransactionResponse,
+}
+
+pub(crate) trait FirestoreTransactionAttempt<T> {
+    fn attempt<'b>(
+        &'b self,
+        db: FirestoreDb,
+        transaction: &'b mut FirestoreTransaction<'_>,
+    ) -> BoxFuture<'b, FirestoreResult<T>>;
+}
+
+impl<T, FN> FirestoreTransactionAttempt<T> for FN
+where
+    for<'b> FN: Fn(FirestoreDb, &'b mut FirestoreTransaction) -> BoxFuture<'b, FirestoreResult<T>>,
+{
+    fn attempt<'b>(
+        &'b self,
+        db: FirestoreDb,
+        transaction: &'b mut FirestoreTransaction<'_>,
+    ) -> BoxFuture<'b, FirestoreResult<T>> {
+        self(db, transaction)
+    }
+}
+
+struct FirestoreTransactionFnAttempt<'f, F>(&'f F);
+
+impl<'f, T, F> FirestoreTransactionAttempt<T> for FirestoreTransactionFnAttempt<'f, F>
+where
+    F: FirestoreTransactionFn<T>,
+{
+    fn attempt<'b>(
+        &'b self,
+        db: FirestoreDb,
+        transaction: &'b mut FirestoreTransaction<'_>,
+    ) -> BoxFuture<'b, FirestoreResult<T>> {
+        self.0.run(db, transaction)
+    }
+}
+
 impl FirestoreDb {
     pub async fn begin_transaction(&self) -> FirestoreResult<FirestoreTransaction> {
         Self::begin_transaction_with_options(self, FirestoreTransactionOptions::new()).await
@@ -162,89 +444,181 @@ impl FirestoreDb {
         for<'b> FN:
             Fn(FirestoreDb, &'b mut FirestoreTransaction) -> BoxFuture<'b, FirestoreResult<T>>,
     {
-        // Perform our initial attempt. If this fails and the backend tells us we can retry,
-        // we'll try again with exponential backoff using the first attempt's transaction ID.
-        let (transaction_id, transaction_span) = {
-            let mut transaction = self.begin_transaction_with_options(options.clone()).await?;
-            let transaction_id = transaction.transaction_id().clone();
-            let transaction_span = transaction.transaction_span.clone();
+        self.run_transaction_attempts(func, options)
+            .await
+            .map(|result| result.value)
+    }
 
-            let cdb = self.clone_with_consistency_selector(
-                FirestoreConsistencySelector::Transaction(transaction_id.clone()),
-            );
+    /// Runs the transaction function with retries, returning its value together with the commit response.
+    pub async fn run_transaction_fn<T, F>(
+        &self,
+        func: &F,
+    ) -> FirestoreResult<FirestoreTransactionRunResult<T>>
+    where
+        F: FirestoreTransactionFn<T>,
+        T: Send,
+    {
+        self.run_transaction_fn_with_options(func, FirestoreTransactionOptions::new())
+            .await
+    }
 
-            let ret_val = func(cdb, &mut transaction).await?;
+    pub async fn run_transaction_fn_with_options<T, F>(
+        &self,
+        func: &F,
+        options: FirestoreTransactionOptions,
+    ) -> FirestoreResult<FirestoreTransactionRunResult<T>>
+    where
+        F: FirestoreTransactionFn<T>,
+        T: Send,
+    {
+        self.run_transaction_attempts(FirestoreTransactionFnAttempt(func), options)
+            .await
+    }
 
-            match transaction.commit().await {
-                Ok(_) => return Ok(ret_val),
-                Err(err) => match err {
-                    FirestoreError::DatabaseError(ref db_err) if db_err.retry_possible => {
-                        transaction_span.in_scope(|| {
-                            warn!("Retryable error occurred in transaction: {}", &err)
-                        });
-                        // Ignore; we'll try again below
-                    }
-                    other => return Err(other),
+    async fn run_transaction_attempts<T, A>(
+        &self,
+        func: A,
+        options: FirestoreTransactionOptions,
+    ) -> FirestoreResult<FirestoreTransactionRunResult<T>>
+    where
+        A: FirestoreTransactionAttempt<T>,
+    {
+        let retry_policy = options
+            .retry_policy
+            .clone()
+            .unwrap_or_else(FirestoreTransactionRetryPolicy::new);
+        let mut backoff = retry_policy.create_backoff(
+            options
+                .max_elapsed_time
+                // Convert to a std `Duration` and clamp any negative durations
+                .map(|v| v.to_std())
+                .transpose()?,
+        );
+
+        // Retries use the first attempt's transaction ID to signal that we're retrying the same transaction.
+        let mut retry_transaction_id: Option<FirestoreTransactionId> = None;
+        let mut attempts: u32 = 0;
+
+        loop {
+            attempts += 1;
+
+            let attempt_options = match retry_transaction_id {
+                Some(ref transaction_id) => FirestoreTransactionOptions {
+                    mode: FirestoreTransactionMode::ReadWriteRetry(transaction_id.clone()),
+                    ..options.clone()
                 },
-            }
-            (transaction_id, transaction_span)
-        };
-
-        // We failed the first time. Now we must change the transaction mode to signal that we're retrying with the original transaction ID.
-        let backoff = ExponentialBackoffBuilder::new()
-            .with_max_elapsed_time(
-                options
-                    .max_elapsed_time
-                    // Convert to a std `Duration` and clamp any negative durations
-                    .map(|v| v.to_std())
-                    .transpose()?,
-            )
-            .build();
-
-        let retry_result = retry(backoff, || async {
-            let options = FirestoreTransactionOptions {
-                mode: FirestoreTransactionMode::ReadWriteRetry(transaction_id.clone()),
-                ..options
+                None => options.clone(),
             };
-            let mut transaction = self.begin_transaction_with_options(options).await?;
+
+            let mut transaction = self.begin_transaction_with_options(attempt_options).await?;
             let transaction_id = transaction.transaction_id().clone();
+            let transaction_span = transaction.transaction_span.clone();
 
             let cdb = self.clone_with_consistency_selector(
                 FirestoreConsistencySelector::Transaction(transaction_id.clone()),
             );
 
-            let ret_val = func(cdb, &mut transaction).await?;
-
-            match transaction.commit().await {
-                Ok(_) => Ok(ret_val),
-                Err(err) => match err {
-                    FirestoreError::DatabaseError(ref db_err) if db_err.retry_possible => {
-                        transaction_span.in_scope(|| debug!("Retrying after error: {}", &err));
-                        Err(backoff::Error::transient(err))
+            let err = match func.attempt(cdb, &mut transaction).await {
+                Ok(value) => match transaction.commit().await {
+                    Ok(response) => {
+                        transaction_span.in_scope(|| {
+                            debug!(
+                                "Transaction has been committed after {} attempt(s)",
+                                attempts
+                            )
+                        });
+                        return Ok(FirestoreTransactionRunResult { value, response });
                     }
-                    e => Err(backoff::Error::permanent(e)),
+                    Err(err) if retry_policy.is_retryable_commit_error(&err) => err,
+                    Err(err) => return Err(err),
                 },
+                Err(err) => {
+                    transaction.rollback().await.ok();
+                    if retry_policy.is_retryable_error(&err) {
+                        err
+                    } else {
+                        return Err(err);
+                    }
+                }
+            };
+
+            if retry_policy.attempts_exhausted(attempts) {
+                transaction_span.in_scope(|| {
+                    error!(
+                        "Unable to commit transaction after {} attempt(s): {}",
+                        attempts, &err
+                    )
+                });
+                return Err(err);
             }
-        })
-        .await;
-
-        if let Err(ref err) = retry_result {
-            transaction_span.in_scope(|| {
-                error!(
-                    "Unable to commit transaction: {}. Trying to roll it back",
-                    &err
-                )
-            });
 
-            let options = FirestoreTransactionOptions {
-                mode: FirestoreTransactionMode::ReadWriteRetry(transaction_id.clone()),
-                ..options
-            };
-            if let Ok(transaction) = self.begin_transaction_with_options(options).await {
-                transaction.rollback().await.ok();
+            match backoff.next_backoff() {
+                Some(delay) => {
+                    transaction_span.in_scope(|| {
+                        warn!(
+                            "Retryable error occurred in transaction attempt {}: {}. Retrying in {:?}",
+                            attempts, &err, delay
+                        )
+                    });
+                    tokio::time::sleep(delay).await;
+                }
+                None => {
+                    transaction_span.in_scope(|| {
+                        error!(
+                            "Unable to commit transaction after {} attempt(s) within the max elapsed time: {}",
+                            attempts, &err
+                        )
+                    });
+                    return Err(err);
+                }
             }
+
+            retry_transaction_id.get_or_insert(transaction_id);
         }
+    }
+}
 
-        retry_result
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::transaction_ops::{CreateObjectOperation, DeleteOperation};
+    use std::collections::HashMap;
+
+    #[test]
+    fn find_unread_writes() {
+        let parent = "projects/test/databases/(default)/documents";
+        let obj: HashMap<String, String> = HashMap::new();
+
+        let writes: Vec<Write> = vec![
+            CreateObjectOperation {
+                parent: parent.to_string(),
+                collection_id: "test".to_string(),
+                document_id: Some("created"),
+                obj: &obj,
+            }
+            .try_into()
+            .unwrap(),
+            DeleteOperation {
+                parent: parent.to_string(),
+                collection_id: "test".to_string(),
+                document_id: "deleted",
+                precondition: None,
+            }
+            .try_into()
+            .unwrap(),
+        ];
+
+        let deleted_path = format!("{}/test/deleted", parent);
+        assert_eq!(
+            FirestoreTransaction::find_unread_write(&writes, &HashSet::new()),
+            Some(deleted_path.as_str())
+        );
+        assert_eq!(
+            FirestoreTransaction::find_unread_write(
+                &writes,
+                &HashSet::from([deleted_path.clone()])
+            ),
+            None
+        );
     }
 }
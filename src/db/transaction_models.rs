@@ -3,12 +3,18 @@ use crate::{FirestoreConsistencySelector, FirestoreWriteResult};
 use chrono::prelude::*;
 use chrono::Duration;
 use rsb_derive::Builder;
+use std::sync::Arc;
 
 #[derive(Debug, Eq, PartialEq, Clone, Builder)]
 pub struct FirestoreTransactionOptions {
     #[default = "FirestoreTransactionMode::ReadWrite"]
     pub mode: FirestoreTransactionMode,
     pub max_elapsed_time: Option<Duration>,
+    pub retry_policy: Option<FirestoreTransactionRetryPolicy>,
+    /// Fail the commit when a document is updated, transformed or deleted
+    /// without being read in the transaction first.
+    #[default = "false"]
+    pub strict_reads: bool,
 }
 
 impl Default for FirestoreTransactionOptions {
@@ -16,10 +22,93 @@ impl Default for FirestoreTransactionOptions {
         Self {
             mode: FirestoreTransactionMode::ReadWrite,
             max_elapsed_time: None,
+            retry_policy: None,
+            strict_reads: false,
         }
     }
 }
 
+pub type FirestoreTransactionRetryPredicate = Arc<dyn Fn(&FirestoreError) -> bool + Send + Sync>;
+
+/// Retry policy for `run_transaction`.
+/// Transactions are retried with exponential backoff when the commit fails with a retryable database error,
+/// or when `retry_if` returns true for the error (including the errors returned from the transaction function).
+/// `max_attempts` limits the total number of attempts, including the first one.
+#[derive(Clone, Builder)]
+pub struct FirestoreTransactionRetryPolicy {
+    #[default = "std::time::Duration::from_millis(500)"]
+    pub initial_interval: std::time::Duration,
+    #[default = "std::time::Duration::from_secs(60)"]
+    pub max_interval: std::time::Duration,
+    #[default = "1.5"]
+    pub multiplier: f64,
+    #[default = "0.5"]
+    pub jitter: f64,
+    pub max_attempts: Option<u32>,
+    pub retry_if: Option<FirestoreTransactionRetryPredicate>,
+}
+
+impl FirestoreTransactionRetryPolicy {
+    pub(crate) fn create_backoff(
+        &self,
+        max_elapsed_time: Option<std::time::Duration>,
+    ) -> backoff::ExponentialBackoff {
+        backoff::ExponentialBackoffBuilder::new()
+            .with_initial_interval(self.initial_interval)
+            .with_max_interval(self.max_interval)
+            .with_multiplier(self.multiplier)
+            .with_randomization_factor(self.jitter)
+            .with_max_elapsed_time(max_elapsed_time)
+            .build()
+    }
+
+    pub(crate) fn attempts_exhausted(&self, attempts: u32) -> bool {
+        self.max_attempts
+            .iter()
+            .any(|max_attempts| attempts >= *max_attempts)
+    }
+
+    pub(crate) fn is_retryable_commit_error(&self, err: &FirestoreError) -> bool {
+        matches!(err, FirestoreError::DatabaseError(ref db_err) if db_err.retry_possible)
+            || self.is_retryable_error(err)
+    }
+
+    pub(crate) fn is_retryable_error(&self, err: &FirestoreError) -> bool {
+        self.retry_if.iter().any(|retry_if| retry_if(err))
+    }
+}
+
+impl std::fmt::Debug for FirestoreTransactionRetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FirestoreTransactionRetryPolicy")
+            .field("initial_interval", &self.initial_interval)
+            .field("max_interval", &self.max_interval)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .field("max_attempts", &self.max_attempts)
+            .field("retry_if", &self.retry_if.as_ref().map(|_| "<predicate>"))
+            .finish()
+    }
+}
+
+// Floats are compared by their bits, so the equality stays reflexive and `Eq` holds
+impl PartialEq for FirestoreTransactionRetryPolicy {
+    fn eq(&self, other: &Self) -> bool {
+        self.initial_interval == other.initial_interval
+            && self.max_interval == other.max_interval
+            && self.multiplier.to_bits() == other.multiplier.to_bits()
+            && self.jitter.to_bits() == other.jitter.to_bits()
+            && self.max_attempts == other.max_attempts
+            && match (&self.retry_if, &other.retry_if) {
+                (Some(left), Some(right)) => Arc::ptr_eq(left, right),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+impl Eq for FirestoreTransactionRetryPolicy {}
+
 impl TryFrom<FirestoreTransactionOptions>
     for gcloud_sdk::google::firestore::v1::TransactionOptions
 {
@@ -90,3 +179,48 @@ pub struct FirestoreTransactionResponse {
     pub write_results: Vec<FirestoreWriteResult>,
     pub commit_time: Option<DateTime<Utc>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::*;
+
+    #[test]
+    fn retry_policy_retryable_errors() {
+        let db_error = |retry_possible| {
+            FirestoreError::DatabaseError(FirestoreDatabaseError::new(
+                FirestoreErrorPublicGenericDetails::new("Aborted".into()),
+                "Test".into(),
+                retry_possible,
+            ))
+        };
+        let user_error =
+            FirestoreError::InvalidParametersError(FirestoreInvalidParametersError::new(
+                FirestoreInvalidParametersPublicDetails::new("test".into(), "Test".into()),
+            ));
+
+        let policy = FirestoreTransactionRetryPolicy::new().with_max_attempts(3);
+        assert!(policy.is_retryable_commit_error(&db_error(true)));
+        assert!(!policy.is_retryable_commit_error(&db_error(false)));
+        assert!(!policy.is_retryable_error(&user_error));
+        assert!(!policy.attempts_exhausted(2));
+        assert!(policy.attempts_exhausted(3));
+
+        let policy = policy.with_retry_if(Arc::new(|err| {
+            matches!(err, FirestoreError::InvalidParametersError(_))
+        }));
+        assert!(policy.is_retryable_error(&user_error));
+        assert!(!policy.is_retryable_error(&db_error(true)));
+    }
+
+    #[test]
+    fn transaction_options_eq() {
+        fn assert_eq_impl<T: Eq>() {}
+        assert_eq_impl::<FirestoreTransactionOptions>();
+
+        let options = FirestoreTransactionOptions::new()
+            .with_retry_policy(FirestoreTransactionRetryPolicy::new().with_multiplier(f64::NAN));
+        assert_eq!(options, options.clone());
+        assert_ne!(options, FirestoreTransactionOptions::new());
+    }
+}
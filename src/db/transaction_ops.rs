@@ -1,7 +1,7 @@
 use crate::db::safe_document_path;
 use crate::{
-    FirestoreDb, FirestoreError, FirestoreFieldTransform, FirestoreResult, FirestoreTransaction,
-    FirestoreWritePrecondition,
+    firestore_update_mask_from_diff, generate_document_id, FirestoreDb, FirestoreError,
+    FirestoreFieldTransform, FirestoreResult, FirestoreTransaction, FirestoreWritePrecondition,
 };
 use gcloud_sdk::google::firestore::v1::Write;
 use serde::Serialize;
@@ -57,6 +57,46 @@ where
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct CreateObjectOperation<'a, T, S>
+where
+    T: Serialize + Sync + Send,
+    S: AsRef<str>,
+{
+    pub parent: String,
+    pub collection_id: String,
+    pub document_id: Option<S>,
+    pub obj: &'a T,
+}
+
+impl<'a, T, S> TryInto<Write> for CreateObjectOperation<'a, T, S>
+where
+    T: Serialize + Sync + Send,
+    S: AsRef<str>,
+{
+    type Error = FirestoreError;
+
+    fn try_into(self) -> Result<Write, Self::Error> {
+        let document_id = self
+            .document_id
+            .as_ref()
+            .map(|document_id| document_id.as_ref().to_string())
+            .unwrap_or_else(generate_document_id);
+
+        Ok(Write {
+            update_mask: None,
+            update_transforms: vec![],
+            current_document: Some(FirestoreWritePrecondition::Exists(false).try_into()?),
+            operation: Some(gcloud_sdk::google::firestore::v1::write::Operation::Update(
+                FirestoreDb::serialize_to_doc(
+                    &safe_document_path(&self.parent, self.collection_id.as_str(), document_id)?,
+                    &self.obj,
+                )?,
+            )),
+        })
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub(crate) struct DeleteOperation<S>
 where
@@ -90,6 +130,26 @@ where
     }
 }
 
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub(crate) struct DeleteDocumentPathOperation {
+    pub document_path: String,
+}
+
+impl TryInto<Write> for DeleteDocumentPathOperation {
+    type Error = FirestoreError;
+
+    fn try_into(self) -> Result<Write, Self::Error> {
+        Ok(Write {
+            update_mask: None,
+            update_transforms: vec![],
+            current_document: None,
+            operation: Some(gcloud_sdk::google::firestore::v1::write::Operation::Delete(
+                self.document_path,
+            )),
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct TransformObjectOperation<S>
 where
@@ -130,7 +190,79 @@ where
     }
 }
 
+pub(crate) fn write_document_path(write: &Write) -> Option<&str> {
+    match write.operation {
+        Some(gcloud_sdk::google::firestore::v1::write::Operation::Update(ref doc)) => {
+            Some(doc.name.as_str())
+        }
+        Some(gcloud_sdk::google::firestore::v1::write::Operation::Delete(ref document_path)) => {
+            Some(document_path.as_str())
+        }
+        Some(gcloud_sdk::google::firestore::v1::write::Operation::Transform(ref transform)) => {
+            Some(transform.document.as_str())
+        }
+        None => None,
+    }
+}
+
+/// Checks if the write has a precondition requiring the document not to exist.
+pub(crate) fn is_create_write(write: &Write) -> bool {
+    matches!(
+        write.current_document,
+        Some(gcloud_sdk::google::firestore::v1::Precondition {
+            condition_type: Some(
+                gcloud_sdk::google::firestore::v1::precondition::ConditionType::Exists(false)
+            ),
+        })
+    )
+}
+
 impl<'a> FirestoreTransaction<'a> {
+    /// Creates a document failing the transaction if it already exists.
+    /// Document ids are generated on the client when not specified.
+    /// Returns the id of the document, including the generated one.
+    pub fn create_object<T, S>(
+        &mut self,
+        collection_id: &str,
+        document_id: Option<S>,
+        obj: &T,
+    ) -> FirestoreResult<String>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        self.create_object_at(
+            self.db.get_documents_path(),
+            collection_id,
+            document_id,
+            obj,
+        )
+    }
+
+    pub fn create_object_at<T, S>(
+        &mut self,
+        parent: &str,
+        collection_id: &str,
+        document_id: Option<S>,
+        obj: &T,
+    ) -> FirestoreResult<String>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        let document_id = document_id
+            .map(|document_id| document_id.as_ref().to_string())
+            .unwrap_or_else(generate_document_id);
+
+        self.add(CreateObjectOperation {
+            parent: parent.to_string(),
+            collection_id: collection_id.to_string(),
+            document_id: Some(document_id.as_str()),
+            obj,
+        })?;
+        Ok(document_id)
+    }
+
     pub fn update_object<T, S>(
         &mut self,
         collection_id: &str,
@@ -180,6 +312,60 @@ impl<'a> FirestoreTransaction<'a> {
         })
     }
 
+    /// Updates only the fields that differ between the `old` and `new` versions of an object.
+    /// Nothing is written when the versions are equal and there are no transforms.
+    pub fn update_object_diff<T, S>(
+        &mut self,
+        collection_id: &str,
+        document_id: S,
+        old: &T,
+        new: &T,
+        precondition: Option<FirestoreWritePrecondition>,
+        update_transforms: Vec<FirestoreFieldTransform>,
+    ) -> FirestoreResult<&mut Self>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        self.update_object_diff_at(
+            self.db.get_documents_path(),
+            collection_id,
+            document_id,
+            old,
+            new,
+            precondition,
+            update_transforms,
+        )
+    }
+
+    pub fn update_object_diff_at<T, S>(
+        &mut self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+        old: &T,
+        new: &T,
+        precondition: Option<FirestoreWritePrecondition>,
+        update_transforms: Vec<FirestoreFieldTransform>,
+    ) -> FirestoreResult<&mut Self>
+    where
+        T: Serialize + Sync + Send,
+        S: AsRef<str>,
+    {
+        match diff_update_params(old, new, precondition, !update_transforms.is_empty())? {
+            Some((update_only, precondition)) => self.update_object_at(
+                parent,
+                collection_id,
+                document_id,
+                new,
+                Some(update_only),
+                precondition,
+                update_transforms,
+            ),
+            None => Ok(self),
+        }
+    }
+
     pub fn delete_by_id<S>(
         &mut self,
         collection_id: &str,
@@ -254,3 +440,85 @@ impl<'a> FirestoreTransaction<'a> {
         })
     }
 }
+
+/// The update mask and precondition to write the differences between two versions of an object,
+/// or `None` when there is nothing to write.
+/// Writes with only transforms require the document to exist, since an empty update mask
+/// would otherwise create a missing document.
+pub(crate) fn diff_update_params<T>(
+    old: &T,
+    new: &T,
+    precondition: Option<FirestoreWritePrecondition>,
+    has_transforms: bool,
+) -> FirestoreResult<Option<(Vec<String>, Option<FirestoreWritePrecondition>)>>
+where
+    T: Serialize,
+{
+    let update_only = firestore_update_mask_from_diff(old, new)?;
+
+    if !update_only.is_empty() {
+        Ok(Some((update_only, precondition)))
+    } else if has_transforms {
+        Ok(Some((
+            update_only,
+            precondition.or(Some(FirestoreWritePrecondition::Exists(true))),
+        )))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn create_object_operation_with_generated_id() {
+        let obj: HashMap<String, String> = HashMap::new();
+        let write: Write = CreateObjectOperation::<_, String> {
+            parent: "projects/test/databases/(default)/documents".to_string(),
+            collection_id: "test".to_string(),
+            document_id: None,
+            obj: &obj,
+        }
+        .try_into()
+        .unwrap();
+
+        assert_eq!(
+            write.current_document,
+            Some(
+                FirestoreWritePrecondition::Exists(false)
+                    .try_into()
+                    .unwrap()
+            )
+        );
+
+        match write.operation {
+            Some(gcloud_sdk::google::firestore::v1::write::Operation::Update(doc)) => {
+                let document_id = doc.name.rsplit('/').next().unwrap();
+                assert_eq!(document_id.len(), 20);
+                assert!(document_id.chars().all(|c| c.is_ascii_alphanumeric()));
+            }
+            _ => panic!("Unexpected write operation"),
+        }
+    }
+
+    #[test]
+    fn diff_update_params_skip_unchanged_objects() {
+        let old: HashMap<String, String> = [("a".to_string(), "1".to_string())].into();
+        let mut new = old.clone();
+
+        assert_eq!(diff_update_params(&old, &new, None, false).unwrap(), None);
+        assert_eq!(
+            diff_update_params(&old, &new, None, true).unwrap(),
+            Some((vec![], Some(FirestoreWritePrecondition::Exists(true))))
+        );
+
+        new.insert("b".to_string(), "2".to_string());
+        assert_eq!(
+            diff_update_params(&old, &new, None, false).unwrap(),
+            Some((vec!["b".to_string()], None))
+        );
+    }
+}
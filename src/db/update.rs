@@ -1,5 +1,8 @@
 use crate::db::safe_document_path;
-use crate::{FirestoreDb, FirestoreResult, FirestoreWritePrecondition};
+use crate::{
+    firestore_update_mask_from_diff, FirestoreDb, FirestoreGetByIdSupport, FirestoreResult,
+    FirestoreWritePrecondition,
+};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use gcloud_sdk::google::firestore::v1::*;
@@ -37,6 +40,39 @@ pub trait FirestoreUpdateSupport {
         for<'de> O: Deserialize<'de>,
         S: AsRef<str> + Send;
 
+    /// Updates only the fields that differ between the `old` and `new` versions of an object,
+    /// including nested fields and fields removed in the `new` version.
+    /// When the versions are equal and the precondition is either missing or `Exists(true)`,
+    /// nothing is written and the current document is returned.
+    async fn update_obj_diff<I, O, S>(
+        &self,
+        collection_id: &str,
+        document_id: S,
+        old: &I,
+        new: &I,
+        return_only_fields: Option<Vec<String>>,
+        precondition: Option<FirestoreWritePrecondition>,
+    ) -> FirestoreResult<O>
+    where
+        I: Serialize + Sync + Send,
+        for<'de> O: Deserialize<'de>,
+        S: AsRef<str> + Send;
+
+    async fn update_obj_diff_at<I, O, S>(
+        &self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+        old: &I,
+        new: &I,
+        return_only_fields: Option<Vec<String>>,
+        precondition: Option<FirestoreWritePrecondition>,
+    ) -> FirestoreResult<O>
+    where
+        I: Serialize + Sync + Send,
+        for<'de> O: Deserialize<'de>,
+        S: AsRef<str> + Send;
+
     async fn update_doc(
         &self,
         collection_id: &str,
@@ -108,6 +144,73 @@ impl FirestoreUpdateSupport for FirestoreDb {
         Self::deserialize_doc_to(&doc)
     }
 
+    async fn update_obj_diff<I, O, S>(
+        &self,
+        collection_id: &str,
+        document_id: S,
+        old: &I,
+        new: &I,
+        return_only_fields: Option<Vec<String>>,
+        precondition: Option<FirestoreWritePrecondition>,
+    ) -> FirestoreResult<O>
+    where
+        I: Serialize + Sync + Send,
+        for<'de> O: Deserialize<'de>,
+        S: AsRef<str> + Send,
+    {
+        self.update_obj_diff_at(
+            self.get_documents_path().as_str(),
+            collection_id,
+            document_id,
+            old,
+            new,
+            return_only_fields,
+            precondition,
+        )
+        .await
+    }
+
+    async fn update_obj_diff_at<I, O, S>(
+        &self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+        old: &I,
+        new: &I,
+        return_only_fields: Option<Vec<String>>,
+        precondition: Option<FirestoreWritePrecondition>,
+    ) -> FirestoreResult<O>
+    where
+        I: Serialize + Sync + Send,
+        for<'de> O: Deserialize<'de>,
+        S: AsRef<str> + Send,
+    {
+        let update_only = firestore_update_mask_from_diff(old, new)?;
+
+        if update_only.is_empty()
+            && matches!(
+                precondition,
+                None | Some(FirestoreWritePrecondition::Exists(true))
+            )
+        {
+            // An update with an empty mask would create a missing document
+            return self
+                .get_obj_at_return_fields(parent, collection_id, document_id, return_only_fields)
+                .await;
+        }
+
+        self.update_obj_at(
+            parent,
+            collection_id,
+            document_id,
+            new,
+            Some(update_only),
+            return_only_fields,
+            precondition,
+        )
+        .await
+    }
+
     async fn update_doc(
         &self,
         collection_id: &str,
@@ -125,7 +228,7 @@ impl FirestoreUpdateSupport for FirestoreDb {
 
         let document_id = firestore_doc.name.clone();
 
-        let update_document_request = tonic::Request::new(UpdateDocumentRequest {
+        let update_document_request = self.create_request(UpdateDocumentRequest {
             update_mask: update_only.map({
                 |vf| DocumentMask {
                     field_paths: vf.iter().map(|f| f.to_string()).collect(),
@@ -136,7 +239,7 @@ impl FirestoreUpdateSupport for FirestoreDb {
                 field_paths: masks.clone(),
             }),
             current_document: precondition.map(|cond| cond.try_into()).transpose()?,
-        });
+        })?;
 
         let begin_query_utc: DateTime<Utc> = Utc::now();
         let update_response = self
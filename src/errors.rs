@@ -5,7 +5,7 @@ use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FirestoreError {
     SystemError(FirestoreSystemError),
     DatabaseError(FirestoreDatabaseError),
@@ -245,7 +245,7 @@ impl serde::de::Error for FirestoreError {
     }
 }
 
-#[derive(Debug, Builder)]
+#[derive(Debug, Clone, Builder)]
 pub struct FirestoreSerializationError {
     pub public: FirestoreErrorPublicGenericDetails,
 }
@@ -295,3 +295,14 @@ impl From<tokio::sync::mpsc::error::SendError<gcloud_sdk::google::firestore::v1:
         ))
     }
 }
+
+impl From<tonic::metadata::errors::InvalidMetadataValue> for FirestoreError {
+    fn from(err: tonic::metadata::errors::InvalidMetadataValue) -> Self {
+        FirestoreError::InvalidParametersError(FirestoreInvalidParametersError::new(
+            FirestoreInvalidParametersPublicDetails::new(
+                "metadata".to_string(),
+                format!("Invalid metadata value: {}", err),
+            ),
+        ))
+    }
+}
@@ -0,0 +1,220 @@
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Binary data stored as Firestore bytes instead of an array of integers.
+#[derive(Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct FirestoreBytes(pub Vec<u8>);
+
+impl From<Vec<u8>> for FirestoreBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        FirestoreBytes(bytes)
+    }
+}
+
+impl From<&[u8]> for FirestoreBytes {
+    fn from(bytes: &[u8]) -> Self {
+        FirestoreBytes(bytes.to_vec())
+    }
+}
+
+impl AsRef<[u8]> for FirestoreBytes {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl Serialize for FirestoreBytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for FirestoreBytes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = FirestoreBytes;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("bytes")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(FirestoreBytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(FirestoreBytes(v))
+            }
+
+            // Binary data previously stored as arrays of integers
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    bytes.push(byte);
+                }
+                Ok(FirestoreBytes(bytes))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+pub mod serialize_as_bytes {
+    use crate::FirestoreBytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S, T>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: AsRef<[u8]>,
+    {
+        serializer.serialize_bytes(bytes.as_ref())
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: From<Vec<u8>>,
+    {
+        FirestoreBytes::deserialize(deserializer).map(|bytes| T::from(bytes.0))
+    }
+}
+
+pub mod serialize_as_optional_bytes {
+    use crate::FirestoreBytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S, T>(bytes: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: AsRef<[u8]>,
+    {
+        match bytes {
+            Some(v) => serializer.serialize_bytes(v.as_ref()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: From<Vec<u8>>,
+    {
+        Option::<FirestoreBytes>::deserialize(deserializer)
+            .map(|bytes| bytes.map(|bytes| T::from(bytes.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firestore_serde::{
+        firestore_document_from_serializable, firestore_document_to_serializable,
+    };
+    use gcloud_sdk::google::firestore::v1::value;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestStructure {
+        #[serde(with = "crate::serialize_as_bytes")]
+        hash: Vec<u8>,
+        #[serde(default)]
+        #[serde(with = "crate::serialize_as_optional_bytes")]
+        signature: Option<Vec<u8>>,
+        payload: FirestoreBytes,
+    }
+
+    #[test]
+    fn serialize_bytes_values() {
+        let test_structure = TestStructure {
+            hash: vec![0, 1, 2, 255],
+            signature: Some(vec![42]),
+            payload: FirestoreBytes(vec![3, 4, 5]),
+        };
+
+        let doc = firestore_document_from_serializable(
+            "projects/test/databases/(default)/documents/test/test-1",
+            &test_structure,
+        )
+        .unwrap();
+
+        assert_eq!(
+            doc.fields.get("hash").and_then(|v| v.value_type.clone()),
+            Some(value::ValueType::BytesValue(vec![0, 1, 2, 255]))
+        );
+        assert_eq!(
+            doc.fields.get("payload").and_then(|v| v.value_type.clone()),
+            Some(value::ValueType::BytesValue(vec![3, 4, 5]))
+        );
+
+        let deserialized: TestStructure = firestore_document_to_serializable(&doc).unwrap();
+        assert_eq!(deserialized, test_structure);
+    }
+
+    #[test]
+    fn deserialize_bytes_from_arrays() {
+        #[derive(Serialize)]
+        struct LegacyStructure {
+            hash: Vec<u8>,
+            payload: Vec<u8>,
+        }
+
+        let doc = firestore_document_from_serializable(
+            "projects/test/databases/(default)/documents/test/test-1",
+            &LegacyStructure {
+                hash: vec![0, 1, 2, 255],
+                payload: vec![3, 4, 5],
+            },
+        )
+        .unwrap();
+
+        let deserialized: TestStructure = firestore_document_to_serializable(&doc).unwrap();
+        assert_eq!(deserialized.hash, vec![0, 1, 2, 255]);
+        assert_eq!(deserialized.signature, None);
+        assert_eq!(deserialized.payload, FirestoreBytes(vec![3, 4, 5]));
+    }
+
+    #[test]
+    fn serialize_serde_bytes_values() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct SerdeBytesStructure {
+            payload: serde_bytes::ByteBuf,
+            #[serde(with = "serde_bytes")]
+            hash: Vec<u8>,
+        }
+
+        let test_structure = SerdeBytesStructure {
+            payload: serde_bytes::ByteBuf::from(vec![0, 1, 2, 255]),
+            hash: vec![3, 4, 5],
+        };
+
+        let doc = firestore_document_from_serializable(
+            "projects/test/databases/(default)/documents/test/test-1",
+            &test_structure,
+        )
+        .unwrap();
+
+        assert_eq!(
+            doc.fields.get("payload").and_then(|v| v.value_type.clone()),
+            Some(value::ValueType::BytesValue(vec![0, 1, 2, 255]))
+        );
+        assert_eq!(
+            doc.fields.get("hash").and_then(|v| v.value_type.clone()),
+            Some(value::ValueType::BytesValue(vec![3, 4, 5]))
+        );
+
+        let deserialized: SerdeBytesStructure = firestore_document_to_serializable(&doc).unwrap();
+        assert_eq!(deserialized, test_structure);
+    }
+}
@@ -356,7 +356,7 @@ impl<'de> serde::Deserializer<'de> for FirestoreValue {
                 visitor.visit_map(FirestoreValueMapAccess::new(v.fields))
             }
             Some(value::ValueType::DoubleValue(v)) => visitor.visit_f64(v),
-            Some(value::ValueType::BytesValue(ref v)) => visitor.visit_bytes(v),
+            Some(value::ValueType::BytesValue(v)) => visitor.visit_byte_buf(v),
             Some(value::ValueType::ReferenceValue(v)) => visitor.visit_string(v),
             Some(value::ValueType::GeoPointValue(v)) => {
                 let lat_lng_fields: HashMap<String, gcloud_sdk::google::firestore::v1::Value> =
@@ -373,6 +373,9 @@ impl<'de> serde::Deserializer<'de> for FirestoreValue {
                     .collect();
                 visitor.visit_map(FirestoreValueMapAccess::new(lat_lng_fields))
             }
+            // Timestamp types read natively from `deserialize_newtype_struct`.
+            // Other types such as plain `DateTime<Utc>` accept only strings,
+            // so timestamps are formatted as RFC3339 (keeping nanoseconds) for them
             Some(value::ValueType::TimestampValue(ts)) => {
                 visitor.visit_string(from_timestamp(ts)?.to_rfc3339())
             }
@@ -523,13 +526,48 @@ impl<'de> serde::Deserializer<'de> for FirestoreValue {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        match (name, self.value.value_type) {
+            (
+                crate::firestore_serde::timestamp_serializers::FIRESTORE_TS_TYPE_TAG_TYPE,
+                Some(value::ValueType::TimestampValue(ts)),
+            ) => visitor.visit_seq(serde::de::value::SeqDeserializer::new(
+                [ts.seconds, ts.nanos as i64].into_iter(),
+            )),
+            (
+                crate::firestore_serde::document_metadata::FIRESTORE_DOCUMENT_TYPE_TAG_TYPE,
+                Some(value::ValueType::MapValue(v)),
+            ) => {
+                let (metadata_fields, data_fields) = v
+                    .fields
+                    .into_iter()
+                    .partition(|(k, _)| k.starts_with("_firestore_"));
+                visitor.visit_seq(FirestoreValueSeqAccess::new(vec![
+                    gcloud_sdk::google::firestore::v1::Value {
+                        value_type: Some(value::ValueType::MapValue(
+                            gcloud_sdk::google::firestore::v1::MapValue {
+                                fields: metadata_fields,
+                            },
+                        )),
+                    },
+                    gcloud_sdk::google::firestore::v1::Value {
+                        value_type: Some(value::ValueType::MapValue(
+                            gcloud_sdk::google::firestore::v1::MapValue {
+                                fields: data_fields,
+                            },
+                        )),
+                    },
+                ]))
+            }
+            (_, value_type) => visitor.visit_newtype_struct(FirestoreValue::from(
+                gcloud_sdk::google::firestore::v1::Value { value_type },
+            )),
+        }
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
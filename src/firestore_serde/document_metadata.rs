@@ -0,0 +1,265 @@
+use crate::FirestoreReference;
+use chrono::{DateTime, Utc};
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+
+pub(crate) const FIRESTORE_DOCUMENT_TYPE_TAG_TYPE: &str = "FirestoreDocument";
+
+/// Metadata of a Firestore document: its ID, location and server timestamps.
+///
+/// The metadata is read-only: it is populated on reading documents and
+/// serializes to nothing, so it can be embedded in your structures using
+/// `#[serde(flatten)]` and the structures are still usable for writes.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "FirestoreDocumentMetadataFields")]
+pub struct FirestoreDocumentMetadata {
+    pub id: String,
+    pub document_path: String,
+    pub parent: String,
+    pub collection_id: String,
+    pub create_time: Option<DateTime<Utc>>,
+    pub update_time: Option<DateTime<Utc>>,
+}
+
+impl FirestoreDocumentMetadata {
+    pub fn reference(&self) -> FirestoreReference {
+        FirestoreReference(self.document_path.clone())
+    }
+}
+
+impl Serialize for FirestoreDocumentMetadata {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+        serializer.serialize_map(Some(0))?.end()
+    }
+}
+
+#[derive(Deserialize)]
+struct FirestoreDocumentMetadataFields {
+    #[serde(rename = "_firestore_id")]
+    id: String,
+    #[serde(rename = "_firestore_full_id")]
+    document_path: String,
+    #[serde(
+        rename = "_firestore_created",
+        default,
+        with = "crate::serialize_as_optional_timestamp"
+    )]
+    create_time: Option<DateTime<Utc>>,
+    #[serde(
+        rename = "_firestore_updated",
+        default,
+        with = "crate::serialize_as_optional_timestamp"
+    )]
+    update_time: Option<DateTime<Utc>>,
+}
+
+impl From<FirestoreDocumentMetadataFields> for FirestoreDocumentMetadata {
+    fn from(fields: FirestoreDocumentMetadataFields) -> Self {
+        let (parent, collection_id) = {
+            let mut parts = fields.document_path.rsplitn(3, '/').skip(1);
+            let collection_id = parts.next().unwrap_or_default().to_string();
+            (parts.next().unwrap_or_default().to_string(), collection_id)
+        };
+
+        Self {
+            id: fields.id,
+            document_path: fields.document_path,
+            parent,
+            collection_id,
+            create_time: fields.create_time,
+            update_time: fields.update_time,
+        }
+    }
+}
+
+/// A document read from Firestore together with its metadata.
+///
+/// Can be used as the result type of any `*_obj` function:
+/// ```rust,ignore
+/// let doc: FirestoreDocument<MyStruct> = db.fluent()
+///     .select()
+///     .by_id_in(COLLECTION)
+///     .obj()
+///     .one("my-id")
+///     .await?
+///     .unwrap();
+/// println!("{} was updated at {:?}", doc.metadata.id, doc.metadata.update_time);
+/// ```
+///
+/// Serializing the document serializes only its data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FirestoreDocument<T> {
+    pub metadata: FirestoreDocumentMetadata,
+    pub data: T,
+}
+
+impl<T> FirestoreDocument<T> {
+    pub fn into_data(self) -> T {
+        self.data
+    }
+}
+
+impl<T> std::ops::Deref for FirestoreDocument<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T> Serialize for FirestoreDocument<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.data.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for FirestoreDocument<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FirestoreDocumentVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> Visitor<'de> for FirestoreDocumentVisitor<T>
+        where
+            T: Deserialize<'de>,
+        {
+            type Value = FirestoreDocument<T>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+                formatter.write_str("a Firestore document")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let metadata = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let data = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
+                Ok(FirestoreDocument { metadata, data })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(
+            FIRESTORE_DOCUMENT_TYPE_TAG_TYPE,
+            FirestoreDocumentVisitor(PhantomData),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firestore_serde::{
+        firestore_document_from_serializable, firestore_document_to_serializable,
+    };
+    use crate::FirestoreTimestamp;
+    use chrono::TimeZone;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct TestStructure {
+        some_string: String,
+        created_at: FirestoreTimestamp,
+    }
+
+    fn test_document() -> gcloud_sdk::google::firestore::v1::Document {
+        let mut doc = firestore_document_from_serializable(
+            "projects/test/databases/(default)/documents/parent/p-1/test/test-1",
+            &TestStructure {
+                some_string: "test".to_string(),
+                created_at: FirestoreTimestamp(Utc.timestamp_opt(1_600_000_000, 123).unwrap()),
+            },
+        )
+        .unwrap();
+        doc.create_time = Some(prost_types::Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 0,
+        });
+        doc.update_time = Some(prost_types::Timestamp {
+            seconds: 1_700_000_100,
+            nanos: 123_456_789,
+        });
+        doc
+    }
+
+    #[test]
+    fn deserialize_document_with_metadata() {
+        let doc: FirestoreDocument<TestStructure> =
+            firestore_document_to_serializable(&test_document()).unwrap();
+
+        assert_eq!(
+            doc.metadata,
+            FirestoreDocumentMetadata {
+                id: "test-1".to_string(),
+                document_path: "projects/test/databases/(default)/documents/parent/p-1/test/test-1"
+                    .to_string(),
+                parent: "projects/test/databases/(default)/documents/parent/p-1".to_string(),
+                collection_id: "test".to_string(),
+                create_time: Some(Utc.timestamp_opt(1_700_000_000, 0).unwrap()),
+                update_time: Some(Utc.timestamp_opt(1_700_000_100, 123_456_789).unwrap()),
+            }
+        );
+        assert_eq!(doc.some_string, "test");
+        assert_eq!(
+            doc.data.created_at,
+            FirestoreTimestamp(Utc.timestamp_opt(1_600_000_000, 123).unwrap())
+        );
+    }
+
+    #[test]
+    fn deserialize_document_data_without_metadata_fields() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(deny_unknown_fields)]
+        struct StrictTestStructure {
+            some_string: String,
+            created_at: FirestoreTimestamp,
+        }
+
+        let doc: FirestoreDocument<StrictTestStructure> =
+            firestore_document_to_serializable(&test_document()).unwrap();
+        assert_eq!(doc.metadata.id, "test-1");
+        assert_eq!(doc.some_string, "test");
+    }
+
+    #[test]
+    fn flatten_document_metadata() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct TestStructureWithMetadata {
+            #[serde(flatten)]
+            metadata: FirestoreDocumentMetadata,
+            some_string: String,
+        }
+
+        let obj: TestStructureWithMetadata =
+            firestore_document_to_serializable(&test_document()).unwrap();
+        assert_eq!(obj.metadata.id, "test-1");
+        assert_eq!(obj.metadata.collection_id, "test");
+        assert_eq!(
+            obj.metadata.update_time,
+            Some(Utc.timestamp_opt(1_700_000_100, 123_456_789).unwrap())
+        );
+
+        let doc = firestore_document_from_serializable(&obj.metadata.document_path, &obj).unwrap();
+        assert_eq!(doc.fields.len(), 1);
+        assert!(doc.fields.contains_key("some_string"));
+    }
+}
@@ -10,6 +10,18 @@ pub use null_serializers::*;
 mod latlng_serializers;
 pub use latlng_serializers::*;
 
+mod reference_serializers;
+pub use reference_serializers::*;
+
+mod bytes_serializers;
+pub use bytes_serializers::*;
+
+mod document_metadata;
+pub use document_metadata::*;
+
+mod update_mask_diff;
+pub use update_mask_diff::*;
+
 use crate::FirestoreValue;
 use gcloud_sdk::google::firestore::v1::Value;
 
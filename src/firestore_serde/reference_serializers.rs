@@ -0,0 +1,500 @@
+use gcloud_sdk::google::firestore::v1::value;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::db::safe_document_path;
+use crate::errors::*;
+use crate::{FirestoreDb, FirestoreGetByIdSupport, FirestoreResult, FirestoreValue};
+
+pub(crate) const FIRESTORE_REFERENCE_TYPE_TAG_TYPE: &str = "FirestoreReference";
+
+/// A reference to a document by its full path,
+/// stored as a reference value instead of a string in Firestore.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct FirestoreReference(pub String);
+
+impl FirestoreReference {
+    pub fn new<S>(parent: &str, collection_id: &str, document_id: S) -> FirestoreResult<Self>
+    where
+        S: AsRef<str> + Send,
+    {
+        Ok(FirestoreReference(safe_document_path(
+            parent,
+            collection_id,
+            document_id,
+        )?))
+    }
+
+    pub fn document_path(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Splits the reference into the parent path, the collection ID and the document ID.
+    pub fn split(&self) -> FirestoreResult<(&str, &str, &str)> {
+        let mut parts = self.0.rsplitn(3, '/');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(document_id), Some(collection_id), Some(parent))
+                if !document_id.is_empty()
+                    && !collection_id.is_empty()
+                    && (parent.ends_with("/documents") || parent.contains("/documents/")) =>
+            {
+                Ok((parent, collection_id, document_id))
+            }
+            _ => Err(FirestoreError::InvalidParametersError(
+                FirestoreInvalidParametersError::new(FirestoreInvalidParametersPublicDetails::new(
+                    "reference".to_string(),
+                    format!("Invalid document reference: {}", self.0),
+                )),
+            )),
+        }
+    }
+
+    pub fn document_id(&self) -> FirestoreResult<&str> {
+        self.split().map(|(_, _, document_id)| document_id)
+    }
+
+    pub fn collection_id(&self) -> FirestoreResult<&str> {
+        self.split().map(|(_, collection_id, _)| collection_id)
+    }
+
+    /// Reads the referenced document as an object.
+    pub async fn get_obj<T, D>(&self, db: &D) -> FirestoreResult<T>
+    where
+        for<'de> T: Deserialize<'de>,
+        D: FirestoreGetByIdSupport,
+    {
+        let (parent, collection_id, document_id) = self.split()?;
+        db.get_obj_at(parent, collection_id, document_id).await
+    }
+
+    /// Reads the referenced document as an object if it exists.
+    pub async fn get_obj_if_exists<T, D>(&self, db: &D) -> FirestoreResult<Option<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+        D: FirestoreGetByIdSupport,
+    {
+        let (parent, collection_id, document_id) = self.split()?;
+        db.get_obj_at_if_exists(parent, collection_id, document_id, None)
+            .await
+    }
+}
+
+impl std::fmt::Display for FirestoreReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<str> for FirestoreReference {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl FirestoreDb {
+    /// A reference to a document in a root collection.
+    pub fn reference<S>(
+        &self,
+        collection_id: &str,
+        document_id: S,
+    ) -> FirestoreResult<FirestoreReference>
+    where
+        S: AsRef<str> + Send,
+    {
+        FirestoreReference::new(self.get_documents_path(), collection_id, document_id)
+    }
+}
+
+pub mod serialize_as_reference {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(document_path: &str, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(
+            crate::firestore_serde::FIRESTORE_REFERENCE_TYPE_TAG_TYPE,
+            &document_path,
+        )
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)
+    }
+}
+
+pub mod serialize_as_optional_reference {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(document_path: &Option<String>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match document_path {
+            Some(v) => serializer.serialize_newtype_struct(
+                crate::firestore_serde::FIRESTORE_REFERENCE_TYPE_TAG_TYPE,
+                v,
+            ),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)
+    }
+}
+
+pub fn serialize_reference_for_firestore<T: ?Sized + Serialize>(
+    value: &T,
+) -> Result<FirestoreValue, FirestoreError> {
+    struct ReferenceSerializer;
+
+    impl Serializer for ReferenceSerializer {
+        type Ok = FirestoreValue;
+        type Error = FirestoreError;
+        type SerializeSeq = crate::firestore_serde::serializer::SerializeVec;
+        type SerializeTuple = crate::firestore_serde::serializer::SerializeVec;
+        type SerializeTupleStruct = crate::firestore_serde::serializer::SerializeVec;
+        type SerializeTupleVariant = crate::firestore_serde::serializer::SerializeTupleVariant;
+        type SerializeMap = crate::firestore_serde::serializer::SerializeMap;
+        type SerializeStruct = crate::firestore_serde::serializer::SerializeMap;
+        type SerializeStructVariant = crate::firestore_serde::serializer::SerializeStructVariant;
+
+        fn serialize_bool(self, _v: bool) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_i8(self, _v: i8) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_i16(self, _v: i16) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_i32(self, _v: i32) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_i64(self, _v: i64) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_u8(self, _v: u8) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_u16(self, _v: u16) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_u32(self, _v: u32) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_u64(self, _v: u64) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_char(self, _v: char) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+            Ok(FirestoreValue::from(
+                gcloud_sdk::google::firestore::v1::Value {
+                    value_type: Some(value::ValueType::ReferenceValue(v.to_string())),
+                },
+            ))
+        }
+
+        fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+            Ok(FirestoreValue::from(
+                gcloud_sdk::google::firestore::v1::Value { value_type: None },
+            ))
+        }
+
+        fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            value.serialize(self)
+        }
+
+        fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+            Ok(FirestoreValue::from(
+                gcloud_sdk::google::firestore::v1::Value { value_type: None },
+            ))
+        }
+
+        fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+            self.serialize_unit()
+        }
+
+        fn serialize_unit_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+        ) -> Result<Self::Ok, Self::Error> {
+            self.serialize_str(variant)
+        }
+
+        fn serialize_newtype_struct<T>(
+            self,
+            _name: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_variant<T>(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            _variant: &'static str,
+            _value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_tuple_struct(
+            self,
+            _name: &'static str,
+            _len: usize,
+        ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            _variant: &'static str,
+            _len: usize,
+        ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_struct(
+            self,
+            _name: &'static str,
+            _len: usize,
+        ) -> Result<Self::SerializeStruct, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            _variant: &'static str,
+            _len: usize,
+        ) -> Result<Self::SerializeStructVariant, Self::Error> {
+            Err(FirestoreError::SerializeError(
+                FirestoreSerializationError::from_message(
+                    "Reference serializer doesn't support this type",
+                ),
+            ))
+        }
+    }
+
+    value.serialize(ReferenceSerializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firestore_serde::{
+        firestore_document_from_serializable, firestore_document_to_serializable,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestStructure {
+        owner: FirestoreReference,
+        #[serde(with = "crate::serialize_as_reference")]
+        parent: String,
+        name: String,
+    }
+
+    #[test]
+    fn serialize_references() {
+        let test_structure = TestStructure {
+            owner: FirestoreReference(
+                "projects/test/databases/(default)/documents/users/user-1".to_string(),
+            ),
+            parent: "projects/test/databases/(default)/documents/groups/group-1/teams/team-1"
+                .to_string(),
+            name: "Test".to_string(),
+        };
+
+        let doc = firestore_document_from_serializable(
+            "projects/test/databases/(default)/documents/test/test-1",
+            &test_structure,
+        )
+        .unwrap();
+
+        assert_eq!(
+            doc.fields.get("owner").and_then(|v| v.value_type.clone()),
+            Some(value::ValueType::ReferenceValue(
+                test_structure.owner.0.clone()
+            ))
+        );
+        assert_eq!(
+            doc.fields.get("parent").and_then(|v| v.value_type.clone()),
+            Some(value::ValueType::ReferenceValue(
+                test_structure.parent.clone()
+            ))
+        );
+        assert_eq!(
+            doc.fields.get("name").and_then(|v| v.value_type.clone()),
+            Some(value::ValueType::StringValue("Test".to_string()))
+        );
+
+        let deserialized: TestStructure = firestore_document_to_serializable(&doc).unwrap();
+        assert_eq!(deserialized, test_structure);
+    }
+
+    #[test]
+    fn split_references() {
+        let reference = FirestoreReference(
+            "projects/test/databases/(default)/documents/groups/group-1/teams/team-1".to_string(),
+        );
+        assert_eq!(
+            reference.split().unwrap(),
+            (
+                "projects/test/databases/(default)/documents/groups/group-1",
+                "teams",
+                "team-1"
+            )
+        );
+
+        let root_reference =
+            FirestoreReference("projects/test/databases/(default)/documents/users/user-1".into());
+        assert_eq!(
+            root_reference.split().unwrap(),
+            (
+                "projects/test/databases/(default)/documents",
+                "users",
+                "user-1"
+            )
+        );
+
+        assert!(FirestoreReference("users/user-1".into()).split().is_err());
+    }
+}
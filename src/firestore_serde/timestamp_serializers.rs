@@ -1,13 +1,15 @@
 use chrono::prelude::*;
 use gcloud_sdk::google::firestore::v1::value;
-use serde::{Deserialize, Serialize, Serializer};
+use serde::de::{Error, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
-    errors::FirestoreSerializationError, timestamp_utils::to_timestamp, FirestoreError,
-    FirestoreValue,
+    errors::FirestoreSerializationError,
+    timestamp_utils::{from_timestamp, to_timestamp},
+    FirestoreError, FirestoreResult, FirestoreValue,
 };
 
-#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, PartialOrd)]
+#[derive(Serialize, Clone, Debug, Eq, PartialEq, PartialOrd)]
 pub struct FirestoreTimestamp(pub DateTime<Utc>);
 
 impl From<DateTime<Utc>> for FirestoreTimestamp {
@@ -16,75 +18,262 @@ impl From<DateTime<Utc>> for FirestoreTimestamp {
     }
 }
 
+impl<'de> Deserialize<'de> for FirestoreTimestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_timestamp(deserializer)
+    }
+}
+
 pub(crate) const FIRESTORE_TS_TYPE_TAG_TYPE: &str = "FirestoreTimestamp";
 
 pub(crate) const FIRESTORE_TS_NULL_TYPE_TAG_TYPE: &str = "FirestoreTimestampAsNull";
 
+/// Types stored as Firestore timestamps.
+/// Deserializing them from Firestore timestamps keeps full nanosecond precision
+/// without formatting and parsing the timestamps as strings.
+///
+/// This applies only to `FirestoreTimestamp` and the fields with `serialize_as_timestamp` attributes.
+/// Other fields, such as `DateTime<Utc>` without the attributes, accept only strings in their `Deserialize`
+/// implementations, so the timestamps are still formatted as RFC3339 strings for them.
+pub trait FirestoreTimestampConvertible: Sized {
+    fn from_firestore_timestamp(ts: prost_types::Timestamp) -> FirestoreResult<Self>;
+
+    fn to_firestore_timestamp(&self) -> prost_types::Timestamp;
+}
+
+impl FirestoreTimestampConvertible for DateTime<Utc> {
+    fn from_firestore_timestamp(ts: prost_types::Timestamp) -> FirestoreResult<Self> {
+        from_timestamp(ts)
+    }
+
+    fn to_firestore_timestamp(&self) -> prost_types::Timestamp {
+        to_timestamp(*self)
+    }
+}
+
+impl FirestoreTimestampConvertible for FirestoreTimestamp {
+    fn from_firestore_timestamp(ts: prost_types::Timestamp) -> FirestoreResult<Self> {
+        from_timestamp(ts).map(FirestoreTimestamp)
+    }
+
+    fn to_firestore_timestamp(&self) -> prost_types::Timestamp {
+        to_timestamp(self.0)
+    }
+}
+
+impl FirestoreTimestampConvertible for prost_types::Timestamp {
+    fn from_firestore_timestamp(ts: prost_types::Timestamp) -> FirestoreResult<Self> {
+        Ok(ts)
+    }
+
+    fn to_firestore_timestamp(&self) -> prost_types::Timestamp {
+        self.clone()
+    }
+}
+
+#[cfg(feature = "time")]
+impl FirestoreTimestampConvertible for time::OffsetDateTime {
+    fn from_firestore_timestamp(ts: prost_types::Timestamp) -> FirestoreResult<Self> {
+        time::OffsetDateTime::from_unix_timestamp_nanos(
+            ts.seconds as i128 * 1_000_000_000 + ts.nanos as i128,
+        )
+        .map_err(|err| {
+            FirestoreError::DeserializeError(FirestoreSerializationError::from_message(format!(
+                "Invalid or out-of-range datetime: {}. {}",
+                ts, err
+            )))
+        })
+    }
+
+    fn to_firestore_timestamp(&self) -> prost_types::Timestamp {
+        prost_types::Timestamp {
+            seconds: self.unix_timestamp(),
+            nanos: self.nanosecond() as i32,
+        }
+    }
+}
+
+/// Deserializes Firestore timestamps from their seconds and nanos,
+/// and from RFC3339 strings for other formats and for the timestamps stored as strings.
+pub(crate) fn deserialize_timestamp<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FirestoreTimestampConvertible,
+{
+    struct TimestampVisitor<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T> Visitor<'de> for TimestampVisitor<T>
+    where
+        T: FirestoreTimestampConvertible,
+    {
+        type Value = T;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a timestamp")
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(self)
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let seconds: i64 = seq
+                .next_element()?
+                .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+            let nanos: i32 = seq
+                .next_element()?
+                .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+            T::from_firestore_timestamp(prost_types::Timestamp { seconds, nanos })
+                .map_err(A::Error::custom)
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            let dt = v.parse::<DateTime<Utc>>().map_err(E::custom)?;
+            T::from_firestore_timestamp(to_timestamp(dt)).map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_newtype_struct(
+        FIRESTORE_TS_TYPE_TAG_TYPE,
+        TimestampVisitor(std::marker::PhantomData),
+    )
+}
+
+fn serialize_timestamp_tagged<S, T>(
+    tag: &'static str,
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: FirestoreTimestampConvertible,
+{
+    let dt = from_timestamp(value.to_firestore_timestamp()).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_newtype_struct(tag, &dt)
+}
+
+/// Stores the values as Firestore timestamps.
+/// Supports `DateTime<Utc>`, `FirestoreTimestamp`, `prost_types::Timestamp`
+/// and `time::OffsetDateTime` with the `time` feature.
 pub mod serialize_as_timestamp {
-    use chrono::{DateTime, Utc};
-    use serde::{Deserialize, Deserializer, Serializer};
+    use crate::FirestoreTimestampConvertible;
+    use serde::{Deserializer, Serializer};
 
-    pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S, T>(date: &T, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
+        T: FirestoreTimestampConvertible,
+    {
+        super::serialize_timestamp_tagged(
+            crate::firestore_serde::FIRESTORE_TS_TYPE_TAG_TYPE,
+            date,
+            serializer,
+        )
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FirestoreTimestampConvertible,
     {
-        serializer
-            .serialize_newtype_struct(crate::firestore_serde::FIRESTORE_TS_TYPE_TAG_TYPE, &date)
+        super::deserialize_timestamp(deserializer)
     }
+}
+
+struct OptionalTimestamp<T>(Option<T>);
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+impl<'de, T> Deserialize<'de> for OptionalTimestamp<T>
+where
+    T: FirestoreTimestampConvertible,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        DateTime::<Utc>::deserialize(deserializer)
+        struct TimestampWrapper<T>(T);
+
+        impl<'de, T> Deserialize<'de> for TimestampWrapper<T>
+        where
+            T: FirestoreTimestampConvertible,
+        {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserialize_timestamp(deserializer).map(TimestampWrapper)
+            }
+        }
+
+        Option::<TimestampWrapper<T>>::deserialize(deserializer)
+            .map(|value| OptionalTimestamp(value.map(|wrapper| wrapper.0)))
     }
 }
 
 pub mod serialize_as_optional_timestamp {
-    use chrono::{DateTime, Utc};
+    use crate::FirestoreTimestampConvertible;
     use serde::{Deserialize, Deserializer, Serializer};
 
-    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S, T>(date: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
+        T: FirestoreTimestampConvertible,
     {
         match date {
-            Some(v) => serializer
-                .serialize_newtype_struct(crate::firestore_serde::FIRESTORE_TS_TYPE_TAG_TYPE, v),
+            Some(v) => super::serialize_timestamp_tagged(
+                crate::firestore_serde::FIRESTORE_TS_TYPE_TAG_TYPE,
+                v,
+                serializer,
+            ),
             None => serializer.serialize_none(),
         }
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
     where
         D: Deserializer<'de>,
+        T: FirestoreTimestampConvertible,
     {
-        Option::<DateTime<Utc>>::deserialize(deserializer)
+        super::OptionalTimestamp::<T>::deserialize(deserializer).map(|value| value.0)
     }
 }
 
 pub mod serialize_as_null_timestamp {
-    use chrono::{DateTime, Utc};
+    use crate::FirestoreTimestampConvertible;
     use serde::{Deserialize, Deserializer, Serializer};
 
-    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S, T>(date: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
+        T: FirestoreTimestampConvertible,
     {
         match date {
-            Some(v) => serializer.serialize_newtype_struct(
+            Some(v) => super::serialize_timestamp_tagged(
                 crate::firestore_serde::FIRESTORE_TS_NULL_TYPE_TAG_TYPE,
                 v,
+                serializer,
             ),
             None => serializer.serialize_none(),
         }
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
     where
         D: Deserializer<'de>,
+        T: FirestoreTimestampConvertible,
     {
-        Option::<DateTime<Utc>>::deserialize(deserializer)
+        super::OptionalTimestamp::<T>::deserialize(deserializer).map(|value| value.0)
     }
 }
 
@@ -367,3 +556,105 @@ pub fn serialize_timestamp_for_firestore<T: ?Sized + Serialize>(
 
     value.serialize(TimestampSerializer { none_as_null })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firestore_serde::{
+        firestore_document_from_serializable, firestore_document_to_serializable,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestStructure {
+        #[serde(with = "crate::serialize_as_timestamp")]
+        created_at: DateTime<Utc>,
+        #[serde(with = "crate::serialize_as_timestamp")]
+        created_at_proto: prost_types::Timestamp,
+        created_at_wrapped: FirestoreTimestamp,
+        #[serde(default)]
+        #[serde(with = "crate::serialize_as_optional_timestamp")]
+        updated_at: Option<DateTime<Utc>>,
+        #[serde(default)]
+        #[serde(with = "crate::serialize_as_null_timestamp")]
+        deleted_at: Option<prost_types::Timestamp>,
+    }
+
+    #[test]
+    fn deserialize_timestamps_with_nanos() {
+        let ts = prost_types::Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 123_456_789,
+        };
+        let dt = from_timestamp(ts.clone()).unwrap();
+
+        let test_structure = TestStructure {
+            created_at: dt,
+            created_at_proto: ts.clone(),
+            created_at_wrapped: FirestoreTimestamp(dt),
+            updated_at: Some(dt),
+            deleted_at: None,
+        };
+
+        let doc = firestore_document_from_serializable(
+            "projects/test/databases/(default)/documents/test/test-1",
+            &test_structure,
+        )
+        .unwrap();
+
+        for field in [
+            "created_at",
+            "created_at_proto",
+            "created_at_wrapped",
+            "updated_at",
+        ] {
+            assert_eq!(
+                doc.fields.get(field).and_then(|v| v.value_type.clone()),
+                Some(value::ValueType::TimestampValue(ts.clone()))
+            );
+        }
+        let deserialized: TestStructure = firestore_document_to_serializable(&doc).unwrap();
+        assert_eq!(deserialized, test_structure);
+    }
+
+    #[test]
+    fn deserialize_timestamps_from_strings() {
+        let mut doc = gcloud_sdk::google::firestore::v1::Document {
+            name: "projects/test/databases/(default)/documents/test/test-1".to_string(),
+            ..Default::default()
+        };
+        for field in ["created_at", "created_at_proto", "created_at_wrapped"] {
+            doc.fields.insert(
+                field.to_string(),
+                gcloud_sdk::google::firestore::v1::Value {
+                    value_type: Some(value::ValueType::StringValue(
+                        "2023-11-14T22:13:20.123456789Z".to_string(),
+                    )),
+                },
+            );
+        }
+
+        let deserialized: TestStructure = firestore_document_to_serializable(&doc).unwrap();
+        assert_eq!(
+            deserialized.created_at_proto,
+            prost_types::Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 123_456_789,
+            }
+        );
+        assert_eq!(deserialized.created_at, deserialized.created_at_wrapped.0);
+        assert_eq!(deserialized.updated_at, None);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn convert_time_timestamps() {
+        let ts = prost_types::Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 123_456_789,
+        };
+        let dt = time::OffsetDateTime::from_firestore_timestamp(ts.clone()).unwrap();
+
+        assert_eq!(dt.nanosecond(), 123_456_789);
+        assert_eq!(dt.to_firestore_timestamp(), ts);
+    }
+}
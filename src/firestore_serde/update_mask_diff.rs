@@ -0,0 +1,173 @@
+use crate::errors::{FirestoreError, FirestoreErrorPublicGenericDetails, FirestoreSystemError};
+use crate::firestore_serde::serializer::FirestoreValueSerializer;
+use crate::FirestoreResult;
+use gcloud_sdk::google::firestore::v1::{value, Value};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Computes the minimal update mask to turn the `old` version of an object into the `new` one.
+///
+/// Both versions are serialized to Firestore values and compared field by field:
+/// - nested maps are compared recursively and produce nested field paths (`a.b.c`);
+/// - arrays and other values are compared as a whole;
+/// - fields missing in the `new` version are included to delete them on update.
+///
+/// Field names that aren't simple identifiers are quoted with backticks.
+pub fn firestore_update_mask_from_diff<T>(old: &T, new: &T) -> FirestoreResult<Vec<String>>
+where
+    T: ?Sized + Serialize,
+{
+    let old_fields = serialize_to_fields(old)?;
+    let new_fields = serialize_to_fields(new)?;
+
+    let mut field_paths = Vec::new();
+    diff_fields(&[], &old_fields, &new_fields, &mut field_paths);
+    field_paths.sort();
+    Ok(field_paths)
+}
+
+fn serialize_to_fields<T>(obj: &T) -> FirestoreResult<HashMap<String, Value>>
+where
+    T: ?Sized + Serialize,
+{
+    match obj
+        .serialize(FirestoreValueSerializer {
+            none_as_null: false,
+        })?
+        .value
+        .value_type
+    {
+        Some(value::ValueType::MapValue(mv)) => Ok(mv.fields),
+        _ => Err(FirestoreError::SystemError(FirestoreSystemError::new(
+            FirestoreErrorPublicGenericDetails::new("SystemError".into()),
+            "Unable to compute update mask from value. No object found".into(),
+        ))),
+    }
+}
+
+fn diff_fields(
+    path: &[&str],
+    old_fields: &HashMap<String, Value>,
+    new_fields: &HashMap<String, Value>,
+    field_paths: &mut Vec<String>,
+) {
+    for (name, new_value) in new_fields {
+        let field_path = [path, &[name.as_str()]].concat();
+        match (
+            old_fields.get(name).map(|v| &v.value_type),
+            &new_value.value_type,
+        ) {
+            (
+                Some(Some(value::ValueType::MapValue(old_map))),
+                Some(value::ValueType::MapValue(new_map)),
+            ) => diff_fields(&field_path, &old_map.fields, &new_map.fields, field_paths),
+            (Some(old_value), new_value) if old_value == new_value => {}
+            _ => field_paths.push(to_field_path(&field_path)),
+        }
+    }
+
+    for name in old_fields.keys() {
+        if !new_fields.contains_key(name) {
+            field_paths.push(to_field_path(&[path, &[name.as_str()]].concat()));
+        }
+    }
+}
+
+fn to_field_path(segments: &[&str]) -> String {
+    segments
+        .iter()
+        .map(|segment| {
+            let is_simple = segment
+                .chars()
+                .next()
+                .map(|c| c.is_ascii_alphabetic() || c == '_')
+                .unwrap_or(false)
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if is_simple {
+                segment.to_string()
+            } else {
+                format!("`{}`", segment.replace('\\', "\\\\").replace('`', "\\`"))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct NestedStructure {
+        some_num: u64,
+        some_string: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct TestStructure {
+        some_id: String,
+        some_num: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        some_opt: Option<String>,
+        some_vec: Vec<u64>,
+        nested: NestedStructure,
+        some_map: BTreeMap<String, String>,
+    }
+
+    fn test_structure() -> TestStructure {
+        TestStructure {
+            some_id: "test-1".to_string(),
+            some_num: 42,
+            some_opt: Some("test".to_string()),
+            some_vec: vec![1, 2, 3],
+            nested: NestedStructure {
+                some_num: 1,
+                some_string: "nested".to_string(),
+            },
+            some_map: BTreeMap::from([("simple".to_string(), "value".to_string())]),
+        }
+    }
+
+    #[test]
+    fn update_mask_for_unchanged_object() {
+        let obj = test_structure();
+        assert_eq!(
+            firestore_update_mask_from_diff(&obj, &obj.clone()).unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn update_mask_for_changed_fields() {
+        let old = test_structure();
+        let new = TestStructure {
+            some_num: 43,
+            some_opt: None,
+            some_vec: vec![1, 2],
+            nested: NestedStructure {
+                some_num: 2,
+                ..old.nested.clone()
+            },
+            some_map: BTreeMap::from([
+                ("other key".to_string(), "value".to_string()),
+                ("simple".to_string(), "value".to_string()),
+            ]),
+            ..old.clone()
+        };
+
+        assert_eq!(
+            firestore_update_mask_from_diff(&old, &new).unwrap(),
+            vec![
+                "nested.some_num",
+                "some_map.`other key`",
+                "some_num",
+                "some_opt",
+                "some_vec",
+            ]
+        );
+    }
+}
@@ -1,6 +1,8 @@
+use crate::query_filter_builder::FirestoreQueryFilterBuilder;
 use crate::{
-    FirestoreBatch, FirestoreBatchWriter, FirestoreDeleteSupport, FirestoreResult,
-    FirestoreTransaction, FirestoreWritePrecondition,
+    FirestoreBatch, FirestoreBatchWriter, FirestoreDeleteOptions, FirestoreDeleteProgress,
+    FirestoreDeleteSupport, FirestoreQueryCollection, FirestoreQueryFilter, FirestoreQueryParams,
+    FirestoreResult, FirestoreTransaction, FirestoreWritePrecondition,
 };
 
 #[derive(Clone, Debug)]
@@ -70,6 +72,20 @@ where
         }
     }
 
+    /// Deletes all documents in the collection matching the filter.
+    #[inline]
+    pub fn filter<FN>(self, filter: FN) -> FirestoreDeleteByQueryBuilder<'a, D>
+    where
+        FN: Fn(FirestoreQueryFilterBuilder) -> Option<FirestoreQueryFilter>,
+    {
+        FirestoreDeleteByQueryBuilder::new(
+            self.db,
+            FirestoreQueryParams::new(FirestoreQueryCollection::Single(self.collection_id))
+                .opt_parent(self.parent),
+        )
+        .filter(filter)
+    }
+
     #[inline]
     pub fn document_id<S>(self, document_id: S) -> FirestoreDeleteExecuteBuilder<'a, D>
     where
@@ -203,3 +219,79 @@ where
         }
     }
 }
+
+#[derive(Clone, Debug)]
+pub struct FirestoreDeleteByQueryBuilder<'a, D>
+where
+    D: FirestoreDeleteSupport,
+{
+    db: &'a D,
+    params: FirestoreQueryParams,
+    options: FirestoreDeleteOptions,
+}
+
+impl<'a, D> FirestoreDeleteByQueryBuilder<'a, D>
+where
+    D: FirestoreDeleteSupport,
+{
+    #[inline]
+    pub(crate) fn new(db: &'a D, params: FirestoreQueryParams) -> Self {
+        Self {
+            db,
+            params,
+            options: FirestoreDeleteOptions::new(),
+        }
+    }
+
+    #[inline]
+    pub fn filter<FN>(self, filter: FN) -> Self
+    where
+        FN: Fn(FirestoreQueryFilterBuilder) -> Option<FirestoreQueryFilter>,
+    {
+        let filter_builder = FirestoreQueryFilterBuilder::new();
+
+        Self {
+            params: self.params.opt_filter(filter(filter_builder)),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn parent<S>(self, parent: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self {
+            params: self.params.with_parent(parent.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn limit(self, value: u32) -> Self {
+        Self {
+            params: self.params.with_limit(value),
+            ..self
+        }
+    }
+
+    /// Deletes the subcollections of the matching documents too.
+    #[inline]
+    pub fn recursive(self) -> Self {
+        Self {
+            options: self.options.with_recursive(true),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn options(self, options: FirestoreDeleteOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    pub async fn execute(self) -> FirestoreResult<FirestoreDeleteProgress> {
+        self.db
+            .delete_by_query_with_options(self.params, self.options)
+            .await
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{FirestoreCreateSupport, FirestoreResult};
+use crate::{
+    FirestoreBatch, FirestoreBatchWriter, FirestoreCreateSupport, FirestoreResult,
+    FirestoreTransaction,
+};
 use gcloud_sdk::google::firestore::v1::Document;
 use serde::{Deserialize, Serialize};
 
@@ -268,4 +271,42 @@ where
                 .await
         }
     }
+
+    /// Adds the document creation to the transaction and returns the id of the document,
+    /// including the generated one.
+    #[inline]
+    pub fn add_to_transaction(
+        self,
+        transaction: &mut FirestoreTransaction<'_>,
+    ) -> FirestoreResult<String> {
+        if let Some(parent) = self.parent {
+            transaction.create_object_at(
+                parent.as_str(),
+                self.collection_id.as_str(),
+                self.document_id,
+                self.object,
+            )
+        } else {
+            transaction.create_object(self.collection_id.as_str(), self.document_id, self.object)
+        }
+    }
+
+    /// Adds the document creation to the batch and returns the id of the document,
+    /// including the generated one.
+    #[inline]
+    pub fn add_to_batch<W>(self, batch: &mut FirestoreBatch<'_, W>) -> FirestoreResult<String>
+    where
+        W: FirestoreBatchWriter,
+    {
+        if let Some(parent) = self.parent {
+            batch.create_object_at(
+                parent.as_str(),
+                self.collection_id.as_str(),
+                self.document_id,
+                self.object,
+            )
+        } else {
+            batch.create_object(self.collection_id.as_str(), self.document_id, self.object)
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use crate::{
     FirestoreQueryFilter, FirestoreQueryFilterCompare, FirestoreQueryFilterComposite,
-    FirestoreQueryFilterUnary, FirestoreValue,
+    FirestoreQueryFilterCompositeOperator, FirestoreQueryFilterUnary, FirestoreValue,
 };
 
 #[derive(Clone, Debug)]
@@ -12,7 +12,11 @@ impl FirestoreQueryFilterBuilder {
     }
 
     #[inline]
-    pub fn for_all<I>(&self, filter_expressions: I) -> Option<FirestoreQueryFilter>
+    fn build_filter_with_op<I>(
+        &self,
+        filter_expressions: I,
+        op: FirestoreQueryFilterCompositeOperator,
+    ) -> Option<FirestoreQueryFilter>
     where
         I: IntoIterator,
         I::Item: FirestoreQueryFilterExpr,
@@ -28,11 +32,35 @@ impl FirestoreQueryFilterBuilder {
             filters.pop()
         } else {
             Some(FirestoreQueryFilter::Composite(
-                FirestoreQueryFilterComposite::new(filters),
+                FirestoreQueryFilterComposite::new(filters).with_operator(op),
             ))
         }
     }
 
+    #[inline]
+    pub fn for_all<I>(&self, filter_expressions: I) -> Option<FirestoreQueryFilter>
+    where
+        I: IntoIterator,
+        I::Item: FirestoreQueryFilterExpr,
+    {
+        self.build_filter_with_op(
+            filter_expressions,
+            FirestoreQueryFilterCompositeOperator::And,
+        )
+    }
+
+    #[inline]
+    pub fn for_any<I>(&self, filter_expressions: I) -> Option<FirestoreQueryFilter>
+    where
+        I: IntoIterator,
+        I::Item: FirestoreQueryFilterExpr,
+    {
+        self.build_filter_with_op(
+            filter_expressions,
+            FirestoreQueryFilterCompositeOperator::Or,
+        )
+    }
+
     #[inline]
     pub fn field<S>(&self, field_name: S) -> FirestoreQueryFilterFieldExpr
     where
@@ -216,3 +244,74 @@ where
         self.and_then(|expr| expr.build_filter())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::fluent_api::query_filter_builder::FirestoreQueryFilterBuilder;
+    use crate::{
+        FirestoreQueryFilter, FirestoreQueryFilterComposite, FirestoreQueryFilterCompositeOperator,
+    };
+
+    #[test]
+    fn composite_filter_defaults_to_and() {
+        let q = FirestoreQueryFilterBuilder::new();
+        let filters = vec![q.field("a").eq(1).unwrap(), q.field("b").eq(2).unwrap()];
+
+        assert_eq!(
+            FirestoreQueryFilterComposite::new(filters.clone()).operator,
+            FirestoreQueryFilterCompositeOperator::And
+        );
+        assert_eq!(
+            FirestoreQueryFilterComposite::new_or(filters).operator,
+            FirestoreQueryFilterCompositeOperator::Or
+        );
+    }
+
+    #[test]
+    fn filter_builder_nested_composite() {
+        let q = FirestoreQueryFilterBuilder::new();
+        let filter = q
+            .for_any([
+                q.field("a").eq(1),
+                q.for_all([q.field("b").eq(2), q.field("c").is_not_null()]),
+            ])
+            .unwrap();
+
+        match filter {
+            FirestoreQueryFilter::Composite(ref composite) => {
+                assert_eq!(
+                    composite.operator,
+                    FirestoreQueryFilterCompositeOperator::Or
+                );
+                assert!(matches!(
+                    composite.for_all_filters[1],
+                    FirestoreQueryFilter::Composite(ref nested)
+                        if nested.operator == FirestoreQueryFilterCompositeOperator::And
+                ));
+            }
+            _ => panic!("Unexpected filter type: {:?}", filter),
+        }
+        assert!(filter.validate().is_ok());
+    }
+
+    #[test]
+    fn filter_builder_disjunctions_limit() {
+        let q = FirestoreQueryFilterBuilder::new();
+        let filter = q
+            .for_all([
+                q.field("a").is_in(vec![1, 2, 3, 4, 5, 6]),
+                q.for_any([q.field("b").eq(1), q.field("c").eq(2), q.field("d").eq(3)]),
+            ])
+            .unwrap();
+        assert!(filter.validate().is_ok());
+
+        let filter = q
+            .for_all([
+                q.field("a").is_in(vec![1, 2, 3, 4, 5, 6, 7, 8]),
+                q.for_any([q.field("b").eq(1), q.field("c").eq(2), q.field("d").eq(3)]),
+                q.field("e").array_contains_any(vec![1, 2]),
+            ])
+            .unwrap();
+        assert!(filter.validate().is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    FirestoreAggregatedQueryParams, FirestoreAggregatedQueryResult,
+    FirestoreAggregatedQuerySupport, FirestoreAggregation, FirestoreAggregationOperator,
+    FirestoreAggregationOperatorCount, FirestoreQueryParams, FirestoreResult,
+};
+use futures::stream::BoxStream;
+use gcloud_sdk::google::firestore::v1::Document;
+use serde::Deserialize;
+use std::marker::PhantomData;
+
+#[derive(Clone, Debug)]
+pub struct FirestoreAggregationBuilder;
+
+impl FirestoreAggregationBuilder {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+
+    #[inline]
+    pub fn fields<I>(&self, aggregation_field_expr: I) -> Vec<FirestoreAggregation>
+    where
+        I: IntoIterator,
+        I::Item: FirestoreAggregationExpr,
+    {
+        aggregation_field_expr
+            .into_iter()
+            .filter_map(|expr| expr.build_aggregation())
+            .collect()
+    }
+
+    #[inline]
+    pub fn field<S>(&self, field_name: S) -> FirestoreAggregationFieldExpr
+    where
+        S: AsRef<str>,
+    {
+        FirestoreAggregationFieldExpr::new(field_name.as_ref().to_string())
+    }
+}
+
+pub trait FirestoreAggregationExpr {
+    fn build_aggregation(self) -> Option<FirestoreAggregation>;
+}
+
+pub struct FirestoreAggregationFieldExpr {
+    field_name: String,
+}
+
+// `sum()` and `avg()` come together with the corresponding `FirestoreAggregationOperator` variants
+impl FirestoreAggregationFieldExpr {
+    pub(crate) fn new(field_name: String) -> Self {
+        Self { field_name }
+    }
+
+    #[inline]
+    pub fn count(self) -> Option<FirestoreAggregation> {
+        Some(FirestoreAggregation::new(self.field_name).with_operator(
+            FirestoreAggregationOperator::Count(FirestoreAggregationOperatorCount::new()),
+        ))
+    }
+
+    #[inline]
+    pub fn count_up_to(self, up_to: usize) -> Option<FirestoreAggregation> {
+        Some(FirestoreAggregation::new(self.field_name).with_operator(
+            FirestoreAggregationOperator::Count(
+                FirestoreAggregationOperatorCount::new().with_up_to(up_to),
+            ),
+        ))
+    }
+}
+
+impl FirestoreAggregationExpr for FirestoreAggregation {
+    #[inline]
+    fn build_aggregation(self) -> Option<FirestoreAggregation> {
+        Some(self)
+    }
+}
+
+impl<F> FirestoreAggregationExpr for Option<F>
+where
+    F: FirestoreAggregationExpr,
+{
+    #[inline]
+    fn build_aggregation(self) -> Option<FirestoreAggregation> {
+        self.and_then(|expr| expr.build_aggregation())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FirestoreAggregatedQueryDocBuilder<'a, D>
+where
+    D: FirestoreAggregatedQuerySupport,
+{
+    db: &'a D,
+    params: FirestoreAggregatedQueryParams,
+}
+
+impl<'a, D> FirestoreAggregatedQueryDocBuilder<'a, D>
+where
+    D: FirestoreAggregatedQuerySupport,
+{
+    #[inline]
+    pub(crate) fn new(
+        db: &'a D,
+        query_params: FirestoreQueryParams,
+        aggregations: Vec<FirestoreAggregation>,
+    ) -> Self {
+        Self {
+            db,
+            params: FirestoreAggregatedQueryParams::new(query_params, aggregations),
+        }
+    }
+
+    #[inline]
+    pub fn obj<T>(self) -> FirestoreAggregatedQueryObjBuilder<'a, D, T>
+    where
+        T: Send,
+        for<'de> T: Deserialize<'de>,
+    {
+        FirestoreAggregatedQueryObjBuilder::new(self.db, self.params)
+    }
+
+    pub async fn query(self) -> FirestoreResult<Vec<Document>> {
+        self.db.aggregated_query_doc(self.params).await
+    }
+
+    pub async fn query_with_metadata(
+        self,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<Document>>> {
+        self.db
+            .aggregated_query_doc_with_metadata(self.params)
+            .await
+    }
+
+    pub async fn stream_query<'b>(self) -> FirestoreResult<BoxStream<'b, Document>> {
+        self.db.stream_aggregated_query_doc(self.params).await
+    }
+
+    pub async fn stream_query_with_errors<'b>(
+        self,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<Document>>> {
+        self.db
+            .stream_aggregated_query_doc_with_errors(self.params)
+            .await
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FirestoreAggregatedQueryObjBuilder<'a, D, T>
+where
+    D: FirestoreAggregatedQuerySupport,
+    T: Send,
+    for<'de> T: Deserialize<'de>,
+{
+    db: &'a D,
+    params: FirestoreAggregatedQueryParams,
+    _pd: PhantomData<T>,
+}
+
+impl<'a, D, T> FirestoreAggregatedQueryObjBuilder<'a, D, T>
+where
+    D: FirestoreAggregatedQuerySupport,
+    T: Send,
+    for<'de> T: Deserialize<'de>,
+{
+    pub(crate) fn new(
+        db: &'a D,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreAggregatedQueryObjBuilder<'a, D, T> {
+        Self {
+            db,
+            params,
+            _pd: PhantomData,
+        }
+    }
+
+    pub async fn query(self) -> FirestoreResult<Vec<T>> {
+        self.db.aggregated_query_obj(self.params).await
+    }
+
+    pub async fn query_with_metadata(
+        self,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<T>>> {
+        self.db
+            .aggregated_query_obj_with_metadata(self.params)
+            .await
+    }
+
+    pub async fn stream_query<'b>(self) -> FirestoreResult<BoxStream<'b, T>> {
+        self.db.stream_aggregated_query_obj(self.params).await
+    }
+
+    pub async fn stream_query_with_errors<'b>(
+        self,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<T>>>
+    where
+        T: 'b,
+    {
+        self.db
+            .stream_aggregated_query_obj_with_errors(self.params)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fluent_api::tests::*;
+    use crate::fluent_api::FirestoreExprBuilder;
+    use crate::{
+        path, FirestoreAggregation, FirestoreAggregationOperator, FirestoreAggregationOperatorCount,
+    };
+
+    #[test]
+    fn select_aggregation_builder_fields() {
+        let builder = FirestoreExprBuilder::new(&mockdb::MockDatabase {})
+            .select()
+            .from("test")
+            .limit(10)
+            .aggregate(|a| {
+                a.fields([
+                    a.field(path!(TestStructure::some_id)).count(),
+                    a.field(path!(TestStructure::some_num)).count_up_to(100),
+                ])
+            });
+
+        assert_eq!(builder.params.query_params.limit, Some(10));
+        assert_eq!(
+            builder.params.aggregations,
+            vec![
+                FirestoreAggregation::new(path!(TestStructure::some_id)).with_operator(
+                    FirestoreAggregationOperator::Count(FirestoreAggregationOperatorCount::new())
+                ),
+                FirestoreAggregation::new(path!(TestStructure::some_num)).with_operator(
+                    FirestoreAggregationOperator::Count(
+                        FirestoreAggregationOperatorCount::new().with_up_to(100)
+                    )
+                ),
+            ]
+        )
+    }
+}
@@ -1,12 +1,16 @@
 use crate::errors::FirestoreError;
 use crate::query_filter_builder::FirestoreQueryFilterBuilder;
+use crate::select_aggregation_builder::{
+    FirestoreAggregatedQueryDocBuilder, FirestoreAggregationBuilder,
+};
 use crate::{
-    FirestoreCollectionDocuments, FirestoreGetByIdSupport, FirestoreListenSupport,
-    FirestoreListener, FirestoreListenerParams, FirestoreListenerTarget,
-    FirestoreListenerTargetParams, FirestorePartition, FirestorePartitionQueryParams,
-    FirestoreQueryCollection, FirestoreQueryCursor, FirestoreQueryFilter, FirestoreQueryOrder,
-    FirestoreQueryParams, FirestoreQuerySupport, FirestoreResult, FirestoreResumeStateStorage,
-    FirestoreTargetType,
+    FirestoreAggregatedQuerySupport, FirestoreAggregation, FirestoreCollectionDocuments,
+    FirestoreGetByIdSupport, FirestoreListenSupport, FirestoreListener, FirestoreListenerParams,
+    FirestoreListenerRetryBackoff, FirestoreListenerTarget, FirestoreListenerTargetParams,
+    FirestorePartition, FirestorePartitionQueryParams, FirestoreQueryCollection,
+    FirestoreQueryCursor, FirestoreQueryFilter, FirestoreQueryOrder, FirestoreQueryParams,
+    FirestoreQuerySnapshotListener, FirestoreQuerySupport, FirestoreResult,
+    FirestoreResumeStateStorage, FirestoreTargetType,
 };
 use futures::stream::BoxStream;
 use gcloud_sdk::google::firestore::v1::Document;
@@ -17,7 +21,12 @@ use std::marker::PhantomData;
 #[derive(Clone, Debug)]
 pub struct FirestoreSelectInitialBuilder<'a, D>
 where
-    D: FirestoreQuerySupport + FirestoreGetByIdSupport + FirestoreListenSupport + Clone + 'static,
+    D: FirestoreQuerySupport
+        + FirestoreGetByIdSupport
+        + FirestoreListenSupport
+        + FirestoreAggregatedQuerySupport
+        + Clone
+        + 'static,
 {
     db: &'a D,
     return_only_fields: Option<Vec<String>>,
@@ -28,6 +37,7 @@ where
     D: FirestoreQuerySupport
         + FirestoreGetByIdSupport
         + FirestoreListenSupport
+        + FirestoreAggregatedQuerySupport
         + Clone
         + Send
         + Sync
@@ -77,7 +87,12 @@ where
 #[derive(Clone, Debug)]
 pub struct FirestoreSelectDocBuilder<'a, D>
 where
-    D: FirestoreQuerySupport + FirestoreListenSupport + Clone + Send + Sync,
+    D: FirestoreQuerySupport
+        + FirestoreListenSupport
+        + FirestoreAggregatedQuerySupport
+        + Clone
+        + Send
+        + Sync,
 {
     db: &'a D,
     params: FirestoreQueryParams,
@@ -85,7 +100,13 @@ where
 
 impl<'a, D> FirestoreSelectDocBuilder<'a, D>
 where
-    D: FirestoreQuerySupport + FirestoreListenSupport + Clone + Send + Sync + 'static,
+    D: FirestoreQuerySupport
+        + FirestoreListenSupport
+        + FirestoreAggregatedQuerySupport
+        + Clone
+        + Send
+        + Sync
+        + 'static,
 {
     #[inline]
     pub(crate) fn new(db: &'a D, params: FirestoreQueryParams) -> Self {
@@ -203,6 +224,18 @@ where
             FirestoreTargetType::Query(self.params),
         )
     }
+
+    #[inline]
+    pub fn aggregate<FN>(self, aggregation: FN) -> FirestoreAggregatedQueryDocBuilder<'a, D>
+    where
+        FN: Fn(FirestoreAggregationBuilder) -> Vec<FirestoreAggregation>,
+    {
+        FirestoreAggregatedQueryDocBuilder::new(
+            self.db,
+            self.params,
+            aggregation(FirestoreAggregationBuilder::new()),
+        )
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -744,6 +777,14 @@ where
         }
     }
 
+    #[inline]
+    pub fn retry_backoff(self, retry_backoff: FirestoreListenerRetryBackoff) -> Self {
+        Self {
+            listener_params: self.listener_params.with_retry_backoff(retry_backoff),
+            ..self
+        }
+    }
+
     #[inline]
     pub fn add_target<S>(
         self,
@@ -761,6 +802,21 @@ where
 
         Ok(())
     }
+
+    #[inline]
+    pub fn add_snapshot_target(
+        self,
+        target: FirestoreListenerTarget,
+        listener: &mut FirestoreQuerySnapshotListener<D>,
+    ) -> FirestoreResult<()> {
+        listener.add_target(FirestoreListenerTargetParams::new(
+            target,
+            self.target_type,
+            self.labels,
+        ))?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -185,6 +185,41 @@ impl FirestoreUpdateSupport for MockDatabase {
         unreachable!()
     }
 
+    async fn update_obj_diff<I, O, S>(
+        &self,
+        collection_id: &str,
+        document_id: S,
+        old: &I,
+        new: &I,
+        return_only_fields: Option<Vec<String>>,
+        precondition: Option<FirestoreWritePrecondition>,
+    ) -> FirestoreResult<O>
+    where
+        I: Serialize + Sync + Send,
+        for<'de> O: Deserialize<'de>,
+        S: AsRef<str> + Send,
+    {
+        unreachable!()
+    }
+
+    async fn update_obj_diff_at<I, O, S>(
+        &self,
+        parent: &str,
+        collection_id: &str,
+        document_id: S,
+        old: &I,
+        new: &I,
+        return_only_fields: Option<Vec<String>>,
+        precondition: Option<FirestoreWritePrecondition>,
+    ) -> FirestoreResult<O>
+    where
+        I: Serialize + Sync + Send,
+        for<'de> O: Deserialize<'de>,
+        S: AsRef<str> + Send,
+    {
+        unreachable!()
+    }
+
     async fn update_doc(
         &self,
         collection_id: &str,
@@ -224,6 +259,36 @@ impl FirestoreDeleteSupport for MockDatabase {
     {
         unreachable!()
     }
+
+    async fn delete_by_query(
+        &self,
+        params: FirestoreQueryParams,
+    ) -> FirestoreResult<FirestoreDeleteProgress> {
+        unreachable!()
+    }
+
+    async fn delete_by_query_with_options(
+        &self,
+        params: FirestoreQueryParams,
+        options: FirestoreDeleteOptions,
+    ) -> FirestoreResult<FirestoreDeleteProgress> {
+        unreachable!()
+    }
+
+    async fn recursive_delete(
+        &self,
+        document_path: &str,
+    ) -> FirestoreResult<FirestoreDeleteProgress> {
+        unreachable!()
+    }
+
+    async fn recursive_delete_with_options(
+        &self,
+        document_path: &str,
+        options: FirestoreDeleteOptions,
+    ) -> FirestoreResult<FirestoreDeleteProgress> {
+        unreachable!()
+    }
 }
 
 #[allow(unused)]
@@ -269,6 +334,27 @@ impl FirestoreListingSupport for MockDatabase {
     {
         unreachable!()
     }
+
+    async fn list_collection_ids(
+        &self,
+        params: FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<FirestoreListCollectionIdsResult> {
+        unreachable!()
+    }
+
+    async fn stream_list_collection_ids(
+        &self,
+        params: FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<BoxStream<String>> {
+        unreachable!()
+    }
+
+    async fn stream_list_collection_ids_with_errors(
+        &self,
+        params: FirestoreListCollectionIdsParams,
+    ) -> FirestoreResult<BoxStream<FirestoreResult<String>>> {
+        unreachable!()
+    }
 }
 
 #[allow(unused)]
@@ -496,4 +582,85 @@ impl FirestoreListenSupport for MockDatabase {
     ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
         unreachable!()
     }
+
+    async fn listen_doc_changes_with_updates<'a, 'b>(
+        &'a self,
+        targets: Vec<FirestoreListenerTargetParams>,
+        target_updates: tokio::sync::mpsc::UnboundedReceiver<FirestoreListenerTargetUpdate>,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<ListenResponse>>> {
+        unreachable!()
+    }
+}
+
+#[allow(unused)]
+#[async_trait]
+impl FirestoreAggregatedQuerySupport for MockDatabase {
+    async fn aggregated_query_doc(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<Document>> {
+        unreachable!()
+    }
+
+    async fn aggregated_query_doc_with_metadata(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<Document>>> {
+        unreachable!()
+    }
+
+    async fn stream_aggregated_query_doc<'b>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, Document>> {
+        unreachable!()
+    }
+
+    async fn stream_aggregated_query_doc_with_errors<'b>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<Document>>> {
+        unreachable!()
+    }
+
+    async fn aggregated_query_obj<T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        unreachable!()
+    }
+
+    async fn aggregated_query_obj_with_metadata<T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<Vec<FirestoreAggregatedQueryResult<T>>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        unreachable!()
+    }
+
+    async fn stream_aggregated_query_obj<'b, T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        unreachable!()
+    }
+
+    async fn stream_aggregated_query_obj_with_errors<'b, T>(
+        &self,
+        params: FirestoreAggregatedQueryParams,
+    ) -> FirestoreResult<BoxStream<'b, FirestoreResult<T>>>
+    where
+        for<'de> T: Deserialize<'de>,
+        T: Send + 'b,
+    {
+        unreachable!()
+    }
 }
@@ -276,6 +276,24 @@ where
         )
     }
 
+    /// Updates only the fields that differ between the `old` and `new` versions of an object.
+    /// The computed update mask replaces the fields specified with `fields`.
+    #[inline]
+    pub fn object_diff<T>(
+        self,
+        old: &'a T,
+        new: &'a T,
+    ) -> FirestoreUpdateObjExecuteBuilder<'a, D, T>
+    where
+        T: Serialize + Sync + Send,
+        for<'de> T: Deserialize<'de>,
+    {
+        FirestoreUpdateObjExecuteBuilder {
+            old_object: Some(old),
+            ..self.object(new)
+        }
+    }
+
     #[inline]
     pub fn transforms<FN>(self, doc_transform: FN) -> Self
     where
@@ -312,6 +330,7 @@ where
     parent: Option<String>,
     document_id: String,
     object: &'a T,
+    old_object: Option<&'a T>,
     return_only_fields: Option<Vec<String>>,
     precondition: Option<FirestoreWritePrecondition>,
     transforms: Vec<FirestoreFieldTransform>,
@@ -341,6 +360,7 @@ where
             parent,
             document_id,
             object,
+            old_object: None,
             return_only_fields,
             precondition,
             transforms,
@@ -351,6 +371,33 @@ where
     where
         for<'de> O: Deserialize<'de>,
     {
+        if let Some(old_object) = self.old_object {
+            return if let Some(parent) = self.parent {
+                self.db
+                    .update_obj_diff_at(
+                        parent.as_str(),
+                        self.collection_id.as_str(),
+                        self.document_id,
+                        old_object,
+                        self.object,
+                        self.return_only_fields,
+                        self.precondition,
+                    )
+                    .await
+            } else {
+                self.db
+                    .update_obj_diff(
+                        self.collection_id.as_str(),
+                        self.document_id,
+                        old_object,
+                        self.object,
+                        self.return_only_fields,
+                        self.precondition,
+                    )
+                    .await
+            };
+        }
+
         if let Some(parent) = self.parent {
             self.db
                 .update_obj_at(
@@ -393,6 +440,29 @@ where
         self,
         transaction: &'a mut FirestoreTransaction<'t>,
     ) -> FirestoreResult<&'a mut FirestoreTransaction<'t>> {
+        if let Some(old_object) = self.old_object {
+            return if let Some(parent) = self.parent {
+                transaction.update_object_diff_at(
+                    parent.as_str(),
+                    self.collection_id.as_str(),
+                    self.document_id,
+                    old_object,
+                    self.object,
+                    self.precondition,
+                    self.transforms,
+                )
+            } else {
+                transaction.update_object_diff(
+                    self.collection_id.as_str(),
+                    self.document_id,
+                    old_object,
+                    self.object,
+                    self.precondition,
+                    self.transforms,
+                )
+            };
+        }
+
         if let Some(parent) = self.parent {
             transaction.update_object_at(
                 parent.as_str(),
@@ -423,6 +493,29 @@ where
     where
         W: FirestoreBatchWriter,
     {
+        if let Some(old_object) = self.old_object {
+            return if let Some(parent) = self.parent {
+                batch.update_object_diff_at(
+                    parent.as_str(),
+                    self.collection_id.as_str(),
+                    self.document_id,
+                    old_object,
+                    self.object,
+                    self.precondition,
+                    self.transforms,
+                )
+            } else {
+                batch.update_object_diff(
+                    self.collection_id.as_str(),
+                    self.document_id,
+                    old_object,
+                    self.object,
+                    self.precondition,
+                    self.transforms,
+                )
+            };
+        }
+
         if let Some(parent) = self.parent {
             batch.update_object_at(
                 parent.as_str(),